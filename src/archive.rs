@@ -0,0 +1,117 @@
+use anyhow::Result;
+use google_drive3::api::File;
+use std::{
+    fs::File as FsFile,
+    io::{Read, Stdout, Write},
+};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+/// Writes downloaded files into a single archive. The format is picked from
+/// the file name: `.zip`, `.tar.zst`/`.tzst` or plain `.tar`; `-` writes an
+/// uncompressed tar stream to stdout.
+pub enum ArchiveWriter {
+    Tar(tar::Builder<FsFile>),
+    TarZstd(tar::Builder<zstd::Encoder<'static, FsFile>>),
+    TarStdout(tar::Builder<Stdout>),
+    Zip(Box<ZipWriter<FsFile>>),
+}
+
+impl ArchiveWriter {
+    pub fn create(path: &str) -> Result<Self> {
+        if path == "-" {
+            return Ok(Self::TarStdout(tar::Builder::new(std::io::stdout())));
+        }
+        let lower = path.to_lowercase();
+        if lower.ends_with(".zip") {
+            Ok(Self::Zip(Box::new(ZipWriter::new(FsFile::create(path)?))))
+        } else if lower.ends_with(".tar.zst") || lower.ends_with(".tzst") {
+            let encoder = zstd::Encoder::new(FsFile::create(path)?, 0)?;
+            Ok(Self::TarZstd(tar::Builder::new(encoder)))
+        } else if lower.ends_with(".tar") {
+            Ok(Self::Tar(tar::Builder::new(FsFile::create(path)?)))
+        } else {
+            Err(anyhow::anyhow!(
+                "Unsupported archive '{}', expected .zip, .tar, .tar.zst or -",
+                path
+            ))
+        }
+    }
+
+    /// Appends the `size` bytes read from `data` as `path`, using the Drive
+    /// `modifiedTime` as entry mtime. Content is copied through as it is
+    /// read, so it never has to fit in memory.
+    pub fn append(
+        &mut self,
+        path: &str,
+        file: &File,
+        size: u64,
+        mut data: impl Read,
+    ) -> Result<()> {
+        match self {
+            Self::Tar(builder) => append_tar(builder, path, file, size, data),
+            Self::TarZstd(builder) => append_tar(builder, path, file, size, data),
+            Self::TarStdout(builder) => append_tar(builder, path, file, size, data),
+            Self::Zip(writer) => {
+                let mut options = SimpleFileOptions::default().large_file(size > u32::MAX as u64);
+                if let Some(mtime) = file
+                    .modified_time
+                    .and_then(|t| zip::DateTime::try_from(t.naive_utc()).ok())
+                {
+                    options = options.last_modified_time(mtime);
+                }
+                writer.start_file(path, options)?;
+                std::io::copy(&mut data, writer)?;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::Tar(builder) => builder.into_inner()?.sync_all()?,
+            Self::TarZstd(builder) => builder.into_inner()?.finish()?.sync_all()?,
+            Self::TarStdout(builder) => builder.into_inner()?.flush()?,
+            Self::Zip(writer) => writer.finish()?.sync_all()?,
+        }
+        Ok(())
+    }
+}
+
+fn append_tar<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    file: &File,
+    size: u64,
+    data: impl Read,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(
+        file.modified_time
+            .map(|t| t.timestamp().max(0) as u64)
+            .unwrap_or(0),
+    );
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_tar_writes_the_entry() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let file = File::default();
+        append_tar(&mut builder, "dir/report.txt", &file, 5, &b"hello"[..]).unwrap();
+        let data = builder.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(&data[..]);
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.path().unwrap().to_str(), Some("dir/report.txt"));
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello");
+    }
+}
@@ -1,4 +1,6 @@
-pub use clap::{Parser, Subcommand, ValueEnum};
+use chrono::{DateTime, NaiveDate, Utc};
+pub use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
 
 #[derive(Parser, Debug)]
 #[command(name = "gdrive")]
@@ -16,8 +18,107 @@ pub enum AuthMethod {
     OAuth,
 }
 
+/// Selects Drive files by folder, search query or explicit ID list.
+#[derive(Args, Clone, Debug)]
+#[group(required = true, multiple = false)]
+pub struct SourceArgs {
+    /// Folder ID in Google Drive
+    #[arg(short = 'f', long)]
+    pub folder_id: Option<String>,
+    /// Drive search query in `q` syntax, e.g. "name contains 'invoice'";
+    /// trashed files are always left out
+    #[arg(short = 'q', long)]
+    pub query: Option<String>,
+    /// File with one Drive file ID per line (`-` for stdin)
+    #[arg(long)]
+    pub ids_from: Option<String>,
+}
+
+/// Narrows, sorts and caps a file listing. MIME type, modification time and
+/// owner are sent to Drive as part of the query; name and size are checked
+/// locally.
+#[derive(Args, Debug)]
+pub struct ListFilters {
+    /// Only files of this MIME type; `image/*` matches a whole family
+    #[arg(long)]
+    pub mime: Option<String>,
+    /// Only files whose name matches this glob, e.g. '*.pdf'
+    #[arg(long, value_parser = glob::Pattern::new)]
+    pub name_glob: Option<glob::Pattern>,
+    /// Only files at least this large, e.g. 500K, 10M or 1.5G
+    #[arg(long, value_parser = parse_size)]
+    pub min_size: Option<u64>,
+    /// Only files modified after this time (RFC 3339, or YYYY-MM-DD for
+    /// after that day in UTC)
+    #[arg(long, value_parser = parse_timestamp)]
+    pub modified_after: Option<DateTime<Utc>>,
+    /// Only files owned by this email address
+    #[arg(long)]
+    pub owner: Option<String>,
+    /// Drive sort order, e.g. "modifiedTime desc" or "folder,name"
+    #[arg(long)]
+    pub order_by: Option<String>,
+    /// Stop after this many files; no further pages or folders are read
+    /// once they have been listed
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+/// Which grants `unshare` acts on; a grant must match every option given.
+#[derive(Args, Debug)]
+#[group(required = true, multiple = true)]
+pub struct GrantFilter {
+    /// Only grants to this kind of principal (repeatable)
+    #[arg(long = "type", value_parser = ["user", "group", "domain", "anyone"])]
+    pub types: Vec<String>,
+    /// Only grants with this role (repeatable)
+    #[arg(long = "role", value_parser = ["owner", "organizer", "fileOrganizer", "writer", "commenter", "reader"])]
+    pub roles: Vec<String>,
+    /// Only grants to this domain or to addresses in it (repeatable); add
+    /// --type domain or --type user to match only one of them
+    #[arg(long = "domain")]
+    pub domains: Vec<String>,
+    /// Only grants to this email address (repeatable)
+    #[arg(long = "email")]
+    pub emails: Vec<String>,
+}
+
+/// How file listings are printed.
+#[derive(Clone, Debug, ValueEnum)]
+pub enum OutputFormat {
+    /// `- name (mime)` lines
+    Text,
+    /// Aligned columns with a header
+    Table,
+    /// One JSON array
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// CSV with a header row
+    Csv,
+}
+
+/// How Drive names that are unsafe on the local filesystem are rewritten.
+#[derive(Clone, Debug, ValueEnum)]
+pub enum NameStyle {
+    /// Percent-encode unsafe characters (reversible)
+    Percent,
+    /// Replace unsafe characters with `_`
+    Replace,
+}
+
+/// Which copy of a group of duplicates is kept.
+#[derive(Clone, Debug, ValueEnum)]
+pub enum KeepRule {
+    /// Keep the copy created first
+    Oldest,
+    /// Keep the copy created last
+    Newest,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
+    /// Upload files to Google Drive
     Upload {
         /// Directory containing the PDF files
         #[arg(short, long)]
@@ -29,17 +130,435 @@ pub enum Commands {
         #[arg(short = 'c', long, default_value_t = 1000)]
         concurrency: usize,
     },
+    /// List files in a Google Drive folder
     List {
         #[arg(short = 'f', long)]
         folder_id: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Comma-separated Drive file fields to fetch and show (not used by
+        /// the text format), e.g. id,name,size,md5Checksum,modifiedTime,owners
+        #[arg(long, value_delimiter = ',', default_value = "id,name,mimeType,size")]
+        fields: Vec<String>,
+        /// Include everything in subfolders too
+        #[arg(short = 'r', long)]
+        recursive: bool,
+        /// Print the folder hierarchy with file counts and sizes per folder
+        #[arg(long, requires = "recursive", conflicts_with_all = [
+            "mime", "name_glob", "min_size", "modified_after", "owner", "order_by", "limit",
+        ])]
+        tree: bool,
+        /// How many folder levels deep to list or print
+        #[arg(long, requires = "recursive")]
+        depth: Option<usize>,
+        /// Max folders listed at the same time during recursive walks
+        #[arg(short = 'c', long, default_value_t = 10)]
+        concurrency: usize,
+        #[command(flatten)]
+        filters: ListFilters,
     },
+    /// Download files from Google Drive
     Download {
-        #[arg(short = 'p', long)]
+        #[arg(short = 'p', long, required_unless_present = "archive")]
+        path: Option<String>,
+        #[command(flatten)]
+        source: SourceArgs,
+        /// Max concurrent downloads
+        #[arg(short = 'c', long, default_value_t = 50)]
+        concurrency: usize,
+        /// How to rewrite Drive names that are unsafe as local file names
+        #[arg(long, value_enum, default_value_t = NameStyle::Percent)]
+        name_style: NameStyle,
+        /// Write everything into one .zip, .tar or .tar.zst archive instead
+        /// of `--path` (`-` streams a tar to stdout)
+        #[arg(long, conflicts_with = "path")]
+        archive: Option<String>,
+        /// Download each file as it was at this time (RFC 3339, or YYYY-MM-DD
+        /// for the end of that day in UTC), using its revision history. Native
+        /// Google Docs, Sheets and Slides are not supported and count as failed
+        #[arg(long, value_parser = parse_timestamp)]
+        as_of: Option<DateTime<Utc>>,
+        /// Re-download files whose local copy differs from Drive (size, mtime
+        /// and MD5) instead of skipping every name that exists locally
+        #[arg(long, conflicts_with_all = ["archive", "as_of"])]
+        refresh: bool,
+    },
+    /// Show the authenticated account, storage quota and upload limit
+    About,
+    /// Find files with identical content below a folder
+    Dupes {
+        #[arg(short = 'f', long)]
+        folder_id: String,
+        /// Only count files as duplicates when their names match too
+        #[arg(long)]
+        by_name: bool,
+        /// Move every copy but one per group to the trash
+        #[arg(long)]
+        trash: bool,
+        /// Which copy to keep with `--trash`
+        #[arg(long, value_enum, default_value_t = KeepRule::Oldest)]
+        keep: KeepRule,
+        /// Skip the confirmation prompt of `--trash`
+        #[arg(short = 'y', long, requires = "trash")]
+        yes: bool,
+        /// Max folders listed (and files trashed) at the same time
+        #[arg(short = 'c', long, default_value_t = 10)]
+        concurrency: usize,
+    },
+    /// Record the metadata of everything below a folder in a SQLite database
+    Inventory {
+        #[arg(short = 'f', long)]
+        folder_id: String,
+        /// SQLite database to create or update
+        #[arg(long)]
+        db: String,
+        /// Read all metadata of the whole tree again instead of only what
+        /// changed since the last run
+        #[arg(long)]
+        full: bool,
+        /// Max folders listed at the same time
+        #[arg(short = 'c', long, default_value_t = 10)]
+        concurrency: usize,
+    },
+    /// Look up the ID of a Drive path, or every path of a file ID
+    Resolve {
+        /// Slash-separated Drive path, e.g. "Finance/2026/report.pdf"
+        #[arg(
+            short = 'p',
+            long,
+            required_unless_present = "id",
+            conflicts_with = "id"
+        )]
+        path: Option<String>,
+        /// File or folder ID to find the full path of
+        #[arg(short = 'i', long)]
+        id: Option<String>,
+        /// Folder or shared drive ID that `--path` starts from
+        #[arg(short = 'f', long, default_value = "root")]
+        folder_id: String,
+    },
+    /// Search file contents and names across My Drive and shared drives
+    Search {
+        /// Words that must all appear in the file's content or name
+        terms: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Comma-separated Drive file fields to show (not used by the text
+        /// format); `path` adds the resolved folder path
+        #[arg(long, value_delimiter = ',', default_value = "id,name,path,owners")]
+        fields: Vec<String>,
+        /// Stop after this many results
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Copy a file or folder on the Drive side, without downloading it
+    Cp {
+        /// ID of the file or folder to copy
+        src: String,
+        /// ID of the folder to copy into
+        dest: String,
+        /// Copy a folder with everything inside it
+        #[arg(short = 'r', long)]
+        recursive: bool,
+        /// Also copy custom file properties
+        #[arg(long)]
+        properties: bool,
+        /// Max files copied at the same time
+        #[arg(short = 'c', long, default_value_t = 10)]
+        concurrency: usize,
+    },
+    /// Create a folder, or a whole path of folders with -p
+    Mkdir {
+        /// Slash-separated folder path, e.g. "Clients/Acme/2026"
         path: String,
+        /// Create missing parent folders and accept existing ones
+        #[arg(short = 'p', long)]
+        parents: bool,
+        /// Folder or shared drive ID the path starts from
+        #[arg(short = 'f', long, default_value = "root")]
+        folder_id: String,
+    },
+    /// Create a folder tree from a YAML spec and print the IDs as JSON
+    Provision {
+        /// YAML file with nested folder names
+        #[arg(long)]
+        spec: String,
+        /// Folder or shared drive ID to create the tree in
+        #[arg(short = 'f', long, default_value = "root")]
+        folder_id: String,
+    },
+    /// Move files into another folder
+    Mv {
+        #[command(flatten)]
+        source: SourceArgs,
+        /// ID of the folder to move the files into
+        #[arg(long)]
+        to: String,
+        /// Only print what would be moved
+        #[arg(long)]
+        dry_run: bool,
+        /// Max files moved at the same time
+        #[arg(short = 'c', long, default_value_t = 20)]
+        concurrency: usize,
+    },
+    /// Rename files whose name matches a regular expression
+    Rename {
+        #[command(flatten)]
+        source: SourceArgs,
+        /// Regular expression; the first match in each name is replaced
+        #[arg(long = "match", value_parser = Regex::new)]
+        pattern: Regex,
+        /// Replacement, with placeholders such as {1}, {ext:upper} for a group
+        /// named `ext`, {seq:4} or {createdTime:%Y%m%d}
+        #[arg(long)]
+        to: String,
+        /// Only print the preview table
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+        /// Max files renamed at the same time
+        #[arg(short = 'c', long, default_value_t = 20)]
+        concurrency: usize,
+    },
+    /// Move files to the trash, or delete them for good
+    Rm {
+        #[command(flatten)]
+        source: SourceArgs,
+        #[command(flatten)]
+        filters: ListFilters,
+        /// Delete permanently instead of moving to the trash
+        #[arg(long)]
+        permanent: bool,
+        /// Skip the confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+        /// Max files removed at the same time
+        #[arg(short = 'c', long, default_value_t = 20)]
+        concurrency: usize,
+    },
+    /// Share files and folders as listed in a CSV file
+    Share {
+        /// CSV with `target,principal,role[,expiration]` rows; the principal
+        /// is an email address, `group:` and a group address, a domain or
+        /// `anyone`
+        #[arg(long)]
+        csv: String,
+        /// Do not email people about their new access
+        #[arg(long)]
+        no_notify: bool,
+        /// Max rows applied at the same time
+        #[arg(short = 'c', long, default_value_t = 10)]
+        concurrency: usize,
+    },
+    /// Report who can access everything below a folder
+    AuditSharing {
         #[arg(short = 'f', long)]
         folder_id: String,
-        /// Max concurrent downloads
-        #[arg(short = 'c', long, default_value_t = 50)]
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+        format: OutputFormat,
+        /// Comma-separated report columns to show; `displayName` is also
+        /// available
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "path,id,permissionId,type,role,principal,exposure,inherited,inheritedFrom,expirationTime"
+        )]
+        fields: Vec<String>,
+        /// Only report public links and external principals
+        #[arg(long)]
+        exposed_only: bool,
+        /// Domain counted as internal, besides the account's own (repeatable)
+        #[arg(long = "internal-domain")]
+        internal_domains: Vec<String>,
+        /// Max folders listed and items checked at the same time
+        #[arg(short = 'c', long, default_value_t = 10)]
+        concurrency: usize,
+    },
+    /// Remove or downgrade matching grants on everything below a folder
+    Unshare {
+        #[arg(short = 'f', long)]
+        folder_id: String,
+        #[command(flatten)]
+        filter: GrantFilter,
+        /// Change matching grants to this role instead of removing them
+        #[arg(long, value_parser = ["reader", "commenter", "writer", "fileOrganizer", "organizer"])]
+        downgrade_to: Option<String>,
+        /// Format of the change log printed to stdout
+        #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+        format: OutputFormat,
+        /// Only log what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+        /// Max folders listed and items changed at the same time
+        #[arg(short = 'c', long, default_value_t = 10)]
         concurrency: usize,
     },
+    /// Show everything Drive knows about one file or folder
+    Stat {
+        /// File or folder ID, or a Drive path such as "Finance/2026"
+        target: String,
+        /// Print the raw Drive metadata as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List, restore or empty the trash
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+    /// List or fetch individual revisions of a Drive file
+    Revisions {
+        #[command(subcommand)]
+        command: RevisionCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RevisionCommands {
+    /// List the stored revisions of a file
+    List {
+        /// File ID in Google Drive
+        #[arg(short = 'i', long)]
+        file_id: String,
+    },
+    /// Download the content of a single revision
+    Get {
+        /// File ID in Google Drive
+        #[arg(short = 'i', long)]
+        file_id: String,
+        /// Revision ID, as shown by `revisions list`
+        #[arg(short = 'r', long)]
+        revision_id: String,
+        /// Where to write the revision content
+        #[arg(short = 'o', long)]
+        output: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrashCommands {
+    /// List trashed files, most recently trashed first
+    List {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Comma-separated Drive file fields to fetch and show (not used by
+        /// the text format)
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "id,name,mimeType,size,trashedTime"
+        )]
+        fields: Vec<String>,
+        /// Stop after this many files
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Take files out of the trash
+    Restore {
+        /// IDs of the trashed files
+        #[arg(required_unless_present = "query", conflicts_with = "query")]
+        ids: Vec<String>,
+        /// Restore every trashed file matching this Drive search query
+        #[arg(short = 'q', long)]
+        query: Option<String>,
+        /// Folder to restore into instead of the original one
+        #[arg(long)]
+        to: Option<String>,
+        /// Max files restored at the same time
+        #[arg(short = 'c', long, default_value_t = 20)]
+        concurrency: usize,
+    },
+    /// Permanently delete everything in the trash
+    Empty {
+        /// Empty the trash of this shared drive instead of My Drive
+        #[arg(long)]
+        drive_id: Option<String>,
+        /// Skip the confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+}
+
+/// Parses an RFC 3339 timestamp, or a `YYYY-MM-DD` date meaning the end of
+/// that day in UTC.
+fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(23, 59, 59))
+        .map(|t| t.and_utc())
+        .ok_or_else(|| format!("expected an RFC 3339 timestamp or YYYY-MM-DD, got '{}'", s))
+}
+
+/// Parses a byte count with an optional binary unit (K, M, G or T, with or
+/// without a trailing B), e.g. `500K` or `1.5G`.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_uppercase();
+    let number = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, unit) = match number.char_indices().last() {
+        Some((i, c @ ('K' | 'M' | 'G' | 'T'))) => (&number[..i], c),
+        _ => (number, ' '),
+    };
+    let shift = match unit {
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        'T' => 40,
+        _ => 0,
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n >= 0.0)
+        .map(|n| (n * (1u64 << shift) as f64) as u64)
+        .ok_or_else(|| format!("expected a size such as 500K, 10M or 1.5G, got '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_takes_binary_units() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("10mb"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size(" 2 T "), Ok(2 << 40));
+    }
+
+    #[test]
+    fn parse_size_rejects_garbage() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("-1K").is_err());
+        assert!(parse_size("ten").is_err());
+    }
+
+    #[test]
+    fn parse_timestamp_takes_rfc3339_and_dates() {
+        assert_eq!(
+            parse_timestamp("2026-01-02T03:04:05+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2026-01-02T01:04:05+00:00"
+        );
+        assert_eq!(
+            parse_timestamp("2026-01-02").unwrap().to_rfc3339(),
+            "2026-01-02T23:59:59+00:00"
+        );
+        assert!(parse_timestamp("02/01/2026").is_err());
+    }
 }
@@ -0,0 +1,863 @@
+use crate::{
+    archive::ArchiveWriter,
+    cli::{NameStyle, SourceArgs},
+    naming::{
+        LocalNamer, assign_local_names, report_name_collisions, report_sanitized_names, safe_join,
+    },
+    output::format_size,
+    revisions::pin_revision,
+    types::{DownloadTarget, TDriveHub},
+    utils::{
+        FILE_FIELDS, FOLDER_MIME_TYPE, paths_from_listings, stream_source_files, walk_tree,
+        with_retry,
+    },
+};
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt, future::ready, stream::BoxStream};
+use google_drive3::{api::File, common::Body};
+use http_body_util::BodyExt;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use md5::{Digest, Md5};
+use std::{
+    collections::HashSet,
+    fs::File as FsFile,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Instant, SystemTime},
+};
+use tokio::sync::mpsc;
+
+pub async fn download_files(
+    hub: TDriveHub,
+    source: &SourceArgs,
+    path: &String,
+    max_concurrency: usize,
+    name_style: &NameStyle,
+    as_of: Option<DateTime<Utc>>,
+    refresh: bool,
+) -> Result<()> {
+    let start_time = Instant::now();
+    println!("Starting bulk download from Google Drive");
+    println!("Target directory: {}", path);
+
+    std::fs::create_dir_all(path)?;
+
+    // download onlly new files, plus changed ones when refreshing
+    let existing_files = get_existing_local_files(path)?;
+    if !existing_files.is_empty() {
+        println!(
+            "Found {} existing files locally, will {}",
+            existing_files.len(),
+            if refresh {
+                "re-download those changed on Drive"
+            } else {
+                "skip them"
+            }
+        );
+    }
+
+    // Oldest first, so the oldest of several same-named files keeps the name.
+    // Folders are filtered out.
+    let mut files = stream_source_files(
+        &hub,
+        source,
+        FILE_FIELDS,
+        max_concurrency,
+        Some("createdTime"),
+    )?
+    .try_filter(|f| ready(f.mime_type.as_deref() != Some(FOLDER_MIME_TYPE)));
+
+    // Downloads start while the rest is still being listed, so the pool is
+    // sized from the first page
+    let first_page: Vec<Result<File>> = (&mut files).take(1000).collect().await;
+    if first_page.is_empty() {
+        println!("No files found for the given source");
+        return Ok(());
+    }
+    let optimal_concurrency =
+        calculate_download_concurrency(first_page.iter().flatten(), max_concurrency);
+    println!("Using {} concurrent downloads", optimal_concurrency);
+
+    let multi_progress = Arc::new(MultiProgress::new());
+    let overall_progress = multi_progress.add(ProgressBar::new(0));
+    overall_progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Downloading [{bar:40.cyan/blue}] {pos}/{len} files ({percent}%) | {elapsed_precise} | ETA: {eta_precise}")
+            .unwrap()
+            .progress_chars("█▓▒░ ")
+    );
+
+    let success_count = Arc::new(AtomicUsize::new(0));
+    let failure_count = Arc::new(AtomicUsize::new(0));
+    let retry_count = Arc::new(AtomicUsize::new(0));
+    let bytes_downloaded = Arc::new(AtomicUsize::new(0));
+    let mut skipped = 0;
+    let mut namer = LocalNamer::new(name_style);
+    let mut listing_error = None;
+
+    let lookups = source.ids_from.is_some();
+    futures::stream::iter(first_page)
+        .chain(files)
+        // an ID that cannot be looked up fails on its own; a listing error
+        // ends the stream and is returned after the downloads
+        .scan(&mut listing_error, |error, result| {
+            ready(match result {
+                Ok(file) => Some(Some(file)),
+                Err(e) if lookups => {
+                    failure_count.fetch_add(1, Ordering::Relaxed);
+                    eprintln!("✗ {:#}", e);
+                    Some(None)
+                }
+                Err(e) => {
+                    **error = Some(e);
+                    None
+                }
+            })
+        })
+        .filter_map(ready)
+        .map(|file| namer.assign(file))
+        .map(|target| {
+            let exists = existing_files.contains(&target.local_name);
+            let local_path = Path::new(path).join(&target.local_name);
+            async move {
+                let wanted = !exists
+                    || (refresh
+                        && is_stale_blocking(local_path, target.file.clone())
+                            .await
+                            .unwrap_or_else(|e| {
+                                eprintln!("Could not compare '{}': {}", target.local_name, e);
+                                true
+                            }));
+                (target, wanted)
+            }
+        })
+        // hashing runs on blocking threads, a few files at a time
+        .buffered(optimal_concurrency)
+        .filter_map(|(target, wanted)| {
+            if !wanted {
+                skipped += 1;
+            }
+            ready(wanted.then_some(target))
+        })
+        .map(|target| {
+            let hub = Arc::clone(&hub);
+            let retries = Arc::clone(&retry_count);
+            async move {
+                match as_of {
+                    Some(as_of) => pin_revision(&hub, target, as_of, &retries).await,
+                    None => Ok(Some(target)),
+                }
+            }
+        })
+        .buffered(optimal_concurrency)
+        .filter_map(|pinned| {
+            ready(pinned.unwrap_or_else(|e| {
+                failure_count.fetch_add(1, Ordering::Relaxed);
+                eprintln!("✗ {:#}", e);
+                None
+            }))
+        })
+        .map(|target| {
+            let hub = Arc::clone(&hub);
+            let target_dir = path.to_string();
+            let progress = Arc::clone(&multi_progress);
+            let overall_prog = overall_progress.clone();
+            let success = Arc::clone(&success_count);
+            let failure = Arc::clone(&failure_count);
+            let retries = Arc::clone(&retry_count);
+            let bytes = Arc::clone(&bytes_downloaded);
+            overall_prog.inc_length(1);
+
+            async move {
+                let file_name = target.local_name.clone();
+                let file_size: u64 = target.file.size.unwrap_or(0).max(0) as u64;
+
+                let file_progress = progress.add(ProgressBar::new(if file_size > 0 {
+                    file_size
+                } else {
+                    100
+                }));
+                file_progress.set_style(
+                    ProgressStyle::default_bar()
+                        .template(&format!(
+                            "  {{bar:20.green/white}} {{bytes}}/{{total_bytes}} {}",
+                            file_name
+                        ))
+                        .unwrap(),
+                );
+
+                let result = download_file_with_retry(
+                    &hub,
+                    &target,
+                    &target_dir,
+                    &file_progress,
+                    3, // max retries
+                    &retries,
+                    &bytes,
+                )
+                .await;
+
+                file_progress.finish_and_clear();
+                overall_prog.inc(1);
+
+                match result {
+                    Ok(_) => {
+                        success.fetch_add(1, Ordering::Relaxed);
+                        overall_prog.set_message(file_name.clone());
+                        (file_name, Ok(()))
+                    }
+                    Err(e) => {
+                        failure.fetch_add(1, Ordering::Relaxed);
+                        overall_prog.set_message(file_name.clone());
+                        (file_name, Err(e))
+                    }
+                }
+            }
+        })
+        .buffer_unordered(optimal_concurrency)
+        .for_each(|(fname, result)| async move {
+            match result {
+                Ok(_) => {
+                    println!("✓ Downloaded '{}'", fname);
+                }
+                Err(e) => {
+                    eprintln!("✗ Failed '{}': {}", fname, e);
+                }
+            }
+        })
+        .await;
+
+    overall_progress.finish_with_message("Download complete!");
+    report_sanitized_names(&namer.renamed);
+    report_name_collisions(&namer.collisions);
+
+    let elapsed = start_time.elapsed();
+    let success = success_count.load(Ordering::Relaxed);
+    let failed = failure_count.load(Ordering::Relaxed);
+    let retries = retry_count.load(Ordering::Relaxed);
+    let total_bytes = bytes_downloaded.load(Ordering::Relaxed);
+
+    if success + failed == 0 && skipped > 0 && listing_error.is_none() {
+        println!("All files already exist locally. No downloads needed.");
+        return Ok(());
+    }
+
+    println!("\nDownload Summary:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("Successful downloads: {}", success);
+    println!("Failed downloads: {}", failed);
+    println!("Skipped (already local): {}", skipped);
+    println!("Total size downloaded: {}", format_size(total_bytes as u64));
+    println!("Total retries: {}", retries);
+    println!("Total time: {:?}", elapsed);
+    println!(
+        "Average speed: {:.2} files/sec",
+        success as f64 / elapsed.as_secs_f64()
+    );
+
+    if total_bytes > 0 {
+        let mb_per_sec = (total_bytes as f64 / (1024.0 * 1024.0)) / elapsed.as_secs_f64();
+        println!("Download speed: {:.2} MB/s", mb_per_sec);
+    }
+
+    if let Some(e) = listing_error {
+        return Err(e.context("Listing stopped early, some files were not downloaded"));
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} files could not be downloaded", failed));
+    }
+    Ok(())
+}
+
+/// Downloads the selected files straight into a single archive instead of a
+/// directory. Folder sources are walked recursively and keep their hierarchy
+/// as archive paths. Status goes to stderr so the archive can go to stdout.
+///
+/// Up to `max_concurrency` downloads are started ahead, but their content is
+/// streamed into the archive one entry at a time as it arrives, so memory use
+/// does not grow with file size. Files that cannot be fetched are left out
+/// and fail the command at the end; anything that breaks an entry halfway (a dropped download, a full disk, a
+/// closed pipe) stops the whole archive.
+pub async fn download_archive(
+    hub: TDriveHub,
+    source: &SourceArgs,
+    archive: &str,
+    max_concurrency: usize,
+    name_style: &NameStyle,
+    as_of: Option<DateTime<Utc>>,
+) -> Result<()> {
+    let start_time = Instant::now();
+    eprintln!("Starting archive download from Google Drive");
+    eprintln!("Target archive: {}", archive);
+
+    let mut targets = match &source.folder_id {
+        Some(folder_id) => {
+            eprintln!("Source folder ID: {}", folder_id);
+            walk_folder(&hub, folder_id, name_style, max_concurrency)
+        }
+        None => {
+            // oldest first, so the oldest of several same-named files keeps
+            // the name
+            let mut namer = LocalNamer::new(name_style);
+            stream_source_files(
+                &hub,
+                source,
+                FILE_FIELDS,
+                max_concurrency,
+                Some("createdTime"),
+            )?
+            .try_filter(|f| ready(f.mime_type.as_deref() != Some(FOLDER_MIME_TYPE)))
+            .map_ok(move |file| namer.assign(file))
+            .boxed()
+        }
+    };
+
+    // Downloads start while the rest is still being listed, so the pool is
+    // sized from the first page
+    let first_page: Vec<Result<DownloadTarget>> = (&mut targets).take(1000).collect().await;
+    if first_page.is_empty() {
+        eprintln!("No files found for the given source");
+        return Ok(());
+    }
+    let optimal_concurrency = calculate_download_concurrency(
+        first_page.iter().flatten().map(|t| &t.file),
+        max_concurrency,
+    );
+    eprintln!("Using {} concurrent downloads", optimal_concurrency);
+    if let Some(as_of) = as_of {
+        eprintln!("Looking up revisions as of {}", as_of.to_rfc3339());
+    }
+
+    // The archive is written on a blocking thread, fed one entry at a time
+    let mut writer = ArchiveWriter::create(archive)?;
+    let (entries, mut entries_rx) = mpsc::channel::<(String, File, u64, ChunkReader)>(1);
+    let archive_writer = tokio::task::spawn_blocking(move || {
+        while let Some((path, file, size, reader)) = entries_rx.blocking_recv() {
+            writer.append(&path, &file, size, reader)?;
+        }
+        writer.finish()
+    });
+
+    let multi_progress = MultiProgress::new();
+    let overall_progress = multi_progress.add(ProgressBar::new(0));
+    overall_progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Archiving [{bar:40.cyan/blue}] {pos}/{len} files ({percent}%) | {elapsed_precise} | ETA: {eta_precise}")
+            .unwrap()
+            .progress_chars("█▓▒░ ")
+    );
+
+    let retry_count = AtomicUsize::new(0);
+    let mut success = 0;
+    let mut failed = 0;
+    let mut total_bytes = 0;
+    let mut stream_error = None;
+    let mut listing_error = None;
+
+    let lookups = source.ids_from.is_some();
+    // lookups and revision checks that failed before a download started
+    let failed_early = AtomicUsize::new(0);
+    let mut downloads = futures::stream::iter(first_page)
+        .chain(targets)
+        // an ID that cannot be looked up fails on its own; a listing error
+        // ends the stream and stops the archive
+        .scan(&mut listing_error, |error, result| {
+            ready(match result {
+                Ok(target) => Some(Some(target)),
+                Err(e) if lookups => {
+                    failed_early.fetch_add(1, Ordering::Relaxed);
+                    eprintln!("✗ {:#}", e);
+                    Some(None)
+                }
+                Err(e) => {
+                    **error = Some(e);
+                    None
+                }
+            })
+        })
+        .filter_map(ready)
+        .map(|target| {
+            let hub = &hub;
+            let retries = &retry_count;
+            async move {
+                match as_of {
+                    Some(as_of) => pin_revision(hub, target, as_of, retries).await,
+                    None => Ok(Some(target)),
+                }
+            }
+        })
+        .buffered(optimal_concurrency)
+        .filter_map(|pinned| {
+            ready(pinned.unwrap_or_else(|e| {
+                failed_early.fetch_add(1, Ordering::Relaxed);
+                eprintln!("✗ {:#}", e);
+                None
+            }))
+        })
+        .map(|target| {
+            let hub = &hub;
+            let retries = &retry_count;
+            overall_progress.inc_length(1);
+            async move {
+                let result = with_retry("Download", &target.local_name, 3, retries, || {
+                    open_content(hub, &target)
+                })
+                .await;
+                (target, result)
+            }
+        })
+        // in order, so each body is read while the ones after it wait
+        .buffered(optimal_concurrency);
+
+    while let Some((target, result)) = downloads.next().await {
+        overall_progress.inc(1);
+        let size = target.file.size.and_then(|s| u64::try_from(s).ok());
+        let body = match (result, size) {
+            (Ok(body), Some(_)) => body,
+            (Ok(_), None) => {
+                failed += 1;
+                eprintln!(
+                    "✗ Failed '{}': Drive reports no size, cannot archive it",
+                    target.local_name
+                );
+                continue;
+            }
+            (Err(e), _) => {
+                failed += 1;
+                eprintln!("✗ Failed '{}': {}", target.local_name, e);
+                continue;
+            }
+        };
+        let size = size.unwrap_or_default();
+
+        let file_progress = multi_progress.add(ProgressBar::new(size.max(1)));
+        file_progress.set_style(
+            ProgressStyle::default_bar()
+                .template(&format!(
+                    "  {{bar:20.green/white}} {{bytes}}/{{total_bytes}} {}",
+                    target.local_name
+                ))
+                .unwrap(),
+        );
+        let (chunks, chunks_rx) = mpsc::channel(16);
+        let reader = ChunkReader {
+            chunks: chunks_rx,
+            current: Bytes::new(),
+            remaining: size,
+        };
+        let entry = (target.local_name.clone(), target.file.clone(), size, reader);
+        if entries.send(entry).await.is_err() {
+            // the writer stopped, its error is reported below
+            break;
+        }
+        let streamed = stream_body(body, &chunks, &file_progress).await;
+        file_progress.finish_and_clear();
+        match streamed {
+            Ok(len) => {
+                success += 1;
+                total_bytes += len;
+            }
+            Err(e) => {
+                stream_error = Some(e.context(format!("Failed '{}'", target.local_name)));
+                break;
+            }
+        }
+    }
+    drop(downloads);
+    drop(entries);
+    failed += failed_early.load(Ordering::Relaxed);
+
+    let written = archive_writer.await?;
+    overall_progress.finish_with_message("Archive complete!");
+    let listing_error =
+        listing_error.map(|e| e.context("Listing stopped early, some files are missing"));
+    if let Err(e) = written.and(stream_error.or(listing_error).map_or(Ok(()), Err)) {
+        if archive != "-" {
+            let _ = std::fs::remove_file(archive);
+        }
+        return Err(e.context("Archive is incomplete and was not kept"));
+    }
+
+    let elapsed = start_time.elapsed();
+    eprintln!("\nArchive Summary:");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("Archived files: {}", success);
+    eprintln!("Failed downloads: {}", failed);
+    eprintln!("Total size archived: {}", format_size(total_bytes as u64));
+    eprintln!("Total retries: {}", retry_count.load(Ordering::Relaxed));
+    eprintln!("Total time: {:?}", elapsed);
+    if total_bytes > 0 {
+        let mb_per_sec = (total_bytes as f64 / (1024.0 * 1024.0)) / elapsed.as_secs_f64();
+        eprintln!("Download speed: {:.2} MB/s", mb_per_sec);
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} files could not be downloaded and are missing from the archive",
+            failed
+        ));
+    }
+    Ok(())
+}
+
+/// Feeds the chunks of one download to the archive writer thread. Reading
+/// fails when the download fails or does not deliver exactly the size Drive
+/// reported, so a broken entry is never closed as if it were complete.
+struct ChunkReader {
+    chunks: mpsc::Receiver<Result<Bytes, String>>,
+    current: Bytes,
+    remaining: u64,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.blocking_recv() {
+                Some(Ok(chunk)) => self.current = chunk,
+                Some(Err(e)) => return Err(std::io::Error::other(e)),
+                None if self.remaining == 0 => return Ok(0),
+                None => {
+                    return Err(std::io::Error::other(format!(
+                        "download ended {} bytes short",
+                        self.remaining
+                    )));
+                }
+            }
+        }
+        if self.current.len() as u64 > self.remaining {
+            return Err(std::io::Error::other(
+                "download is larger than the size Drive reported",
+            ));
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Passes the chunks of `body` on to `chunks` as they arrive and returns how
+/// many bytes there were.
+async fn stream_body(
+    mut body: Body,
+    chunks: &mpsc::Sender<Result<Bytes, String>>,
+    progress: &ProgressBar,
+) -> Result<usize> {
+    let mut len = 0;
+    while let Some(frame) = body.frame().await {
+        let chunk = match frame {
+            Ok(frame) => match frame.into_data() {
+                Ok(chunk) => chunk,
+                Err(_) => continue,
+            },
+            Err(e) => {
+                let message = format!("Failed to read frame: {}", e);
+                let _ = chunks.send(Err(message.clone())).await;
+                return Err(anyhow::anyhow!(message));
+            }
+        };
+        len += chunk.len();
+        progress.set_position(len as u64);
+        chunks
+            .send(Ok(chunk))
+            .await
+            .map_err(|_| anyhow::anyhow!("The archive writer stopped"))?;
+    }
+    Ok(len)
+}
+
+/// Streams every file below `folder_id`, pairing each with its sanitized
+/// path relative to that folder (e.g. `2024/reports/q1.pdf`).
+pub fn walk_folder<'a>(
+    hub: &TDriveHub,
+    folder_id: &str,
+    name_style: &'a NameStyle,
+    concurrency: usize,
+) -> BoxStream<'a, Result<DownloadTarget>> {
+    let listings = walk_tree(hub, folder_id, FILE_FIELDS, None, None, concurrency, None);
+    paths_from_listings(listings, folder_id, |children| {
+        assign_local_names(children, name_style)
+            .into_iter()
+            .map(|target| (target.file, target.local_name))
+            .collect()
+    })
+    .try_filter_map(|item| {
+        ready(Ok((item.file.mime_type.as_deref()
+            != Some(FOLDER_MIME_TYPE))
+        .then_some(DownloadTarget {
+            file: item.file,
+            local_name: item.path,
+            revision_id: None,
+        })))
+    })
+    .boxed()
+}
+
+fn get_existing_local_files(path: &str) -> Result<HashSet<String>> {
+    let mut existing = HashSet::new();
+
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.filter_map(Result::ok) {
+            if let Ok(metadata) = entry.metadata()
+                && metadata.is_file()
+            {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                existing.insert(file_name);
+            }
+        }
+    }
+
+    Ok(existing)
+}
+
+/// Whether the local copy at `path` differs from the Drive file. Sizes are
+/// compared first; if the Drive copy is newer, the MD5 checksum decides, and a
+/// matching file gets the Drive mtime so it is not hashed again next time.
+fn is_stale(path: &Path, file: &File) -> Result<bool> {
+    let metadata = std::fs::metadata(path)?;
+    if file.size.is_some_and(|size| size as u64 != metadata.len()) {
+        return Ok(true);
+    }
+    let Some(remote_mtime) = file.modified_time.map(SystemTime::from) else {
+        return Ok(true);
+    };
+    if metadata.modified()? >= remote_mtime {
+        return Ok(false);
+    }
+    match &file.md5_checksum {
+        Some(md5) if *md5 == local_md5(path)? => {
+            FsFile::options()
+                .write(true)
+                .open(path)?
+                .set_modified(remote_mtime)?;
+            Ok(false)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Runs [`is_stale`] on a blocking thread, as hashing a large file would
+/// otherwise stall the downloads sharing this worker.
+async fn is_stale_blocking(path: PathBuf, file: File) -> Result<bool> {
+    tokio::task::spawn_blocking(move || is_stale(&path, &file)).await?
+}
+
+fn local_md5(path: &Path) -> Result<String> {
+    let mut hasher = Md5::new();
+    std::io::copy(&mut FsFile::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn calculate_download_concurrency<'a>(
+    files: impl IntoIterator<Item = &'a File>,
+    max_concurrency: usize,
+) -> usize {
+    let (count, total) = files.into_iter().fold((0u64, 0u64), |(count, total), f| {
+        (count + 1, total + f.size.unwrap_or(0).max(0) as u64)
+    });
+    let avg_size = total.checked_div(count).unwrap_or(0);
+
+    let optimal = if avg_size < 1_000_000 {
+        max_concurrency.min(50)
+    } else if avg_size < 10_000_000 {
+        max_concurrency.min(30)
+    } else if avg_size < 100_000_000 {
+        max_concurrency.min(10)
+    } else {
+        max_concurrency.min(5)
+    };
+
+    optimal.max(1)
+}
+
+async fn download_file_with_retry(
+    hub: &TDriveHub,
+    target: &DownloadTarget,
+    target_dir: &str,
+    progress: &ProgressBar,
+    max_retries: u32,
+    retry_counter: &Arc<AtomicUsize>,
+    bytes_counter: &Arc<AtomicUsize>,
+) -> Result<()> {
+    with_retry(
+        "Download",
+        &target.local_name,
+        max_retries,
+        retry_counter,
+        || download_single_file(hub, target, target_dir, progress, bytes_counter),
+    )
+    .await
+}
+
+async fn download_single_file(
+    hub: &TDriveHub,
+    target: &DownloadTarget,
+    target_dir: &str,
+    progress: &ProgressBar,
+    bytes_counter: &Arc<AtomicUsize>,
+) -> Result<()> {
+    let target_path = safe_join(target_dir, &target.local_name)?;
+    let bytes_vec = fetch_file_bytes(hub, target, progress).await?;
+    // Written next to the target and renamed over it, so readers only ever
+    // see the old or the new content, never a partial file
+    let temp_path = safe_join(target_dir, &format!(".{}.part", target.local_name))?;
+    let mut temp_file = FsFile::create(&temp_path)?;
+    let total_bytes = bytes_vec.len();
+    let written = temp_file.write_all(&bytes_vec).and_then(|_| {
+        if let Some(mtime) = target.file.modified_time {
+            temp_file.set_modified(mtime.into())?;
+        }
+        temp_file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    bytes_counter.fetch_add(total_bytes, Ordering::Relaxed);
+    std::fs::rename(temp_path, target_path)?;
+
+    Ok(())
+}
+
+/// Requests the content of a Drive file (or of the pinned revision). The body
+/// is returned unread, for the caller to consume as it arrives.
+pub async fn open_content(hub: &TDriveHub, target: &DownloadTarget) -> Result<Body> {
+    let file_id = target.file.id.as_deref().unwrap_or_default();
+    let result = match &target.revision_id {
+        Some(revision_id) => hub
+            .revisions()
+            .get(file_id, revision_id)
+            .param("alt", "media")
+            .add_scope(google_drive3::api::Scope::Full)
+            .doit()
+            .await
+            .map(|(response, _)| response),
+        None => hub
+            .files()
+            .get(file_id)
+            .param("alt", "media")
+            .supports_all_drives(true)
+            .add_scope(google_drive3::api::Scope::Full)
+            .doit()
+            .await
+            .map(|(response, _)| response),
+    };
+    let response = result
+        .map_err(|e| anyhow::anyhow!("Failed to download file '{}': {}", target.local_name, e))?;
+    Ok(response.into_body())
+}
+
+/// Downloads the content of a Drive file (or of the pinned revision) into
+/// memory, reporting progress.
+pub async fn fetch_file_bytes(
+    hub: &TDriveHub,
+    target: &DownloadTarget,
+    progress: &ProgressBar,
+) -> Result<Vec<u8>> {
+    let mut body = open_content(hub, target).await?;
+    let mut bytes_vec = Vec::new();
+    while let Some(frame_result) = body.frame().await {
+        let frame = frame_result.map_err(|e| anyhow::anyhow!("Failed to read frame: {}", e))?;
+        if let Some(chunk) = frame.data_ref() {
+            bytes_vec.extend_from_slice(chunk);
+            progress.set_position(bytes_vec.len() as u64);
+        }
+    }
+    Ok(bytes_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(chunks: Vec<Result<&'static str, &'static str>>, size: u64) -> ChunkReader {
+        let (tx, rx) = mpsc::channel(chunks.len().max(1));
+        for chunk in chunks {
+            tx.try_send(
+                chunk
+                    .map(|c| Bytes::from_static(c.as_bytes()))
+                    .map_err(str::to_string),
+            )
+            .unwrap();
+        }
+        ChunkReader {
+            chunks: rx,
+            current: Bytes::new(),
+            remaining: size,
+        }
+    }
+
+    #[test]
+    fn chunk_reader_joins_chunks() {
+        let mut out = String::new();
+        reader(vec![Ok("hello "), Ok("world")], 11)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "hello world");
+    }
+
+    #[test]
+    fn chunk_reader_fails_on_short_long_or_broken_downloads() {
+        let mut out = Vec::new();
+        assert!(reader(vec![Ok("hello")], 11).read_to_end(&mut out).is_err());
+        assert!(
+            reader(vec![Ok("hello world!")], 11)
+                .read_to_end(&mut out)
+                .is_err()
+        );
+        assert!(
+            reader(vec![Ok("hello"), Err("reset")], 11)
+                .read_to_end(&mut out)
+                .is_err()
+        );
+    }
+
+    fn local_file(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gdrive-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn drive_file(content: &str, modified: SystemTime) -> File {
+        File {
+            size: Some(content.len() as i64),
+            md5_checksum: Some(format!("{:x}", Md5::digest(content.as_bytes()))),
+            modified_time: Some(modified.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn is_stale_when_sizes_differ() {
+        let path = local_file("size.txt", "old");
+        let file = drive_file("newer content", SystemTime::now());
+        assert!(is_stale(&path, &file).unwrap());
+    }
+
+    #[test]
+    fn is_not_stale_when_local_copy_is_newer() {
+        let path = local_file("newer.txt", "same");
+        let file = drive_file("same", SystemTime::UNIX_EPOCH);
+        assert!(!is_stale(&path, &file).unwrap());
+    }
+
+    #[test]
+    fn is_stale_decided_by_checksum_when_drive_is_newer() {
+        let later = SystemTime::now() + std::time::Duration::from_secs(3600);
+        let path = local_file("same.txt", "abcd");
+        assert!(!is_stale(&path, &drive_file("abcd", later)).unwrap());
+        // a match takes the Drive mtime, so it is not hashed again
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), later);
+
+        let path = local_file("changed.txt", "abcd");
+        assert!(is_stale(&path, &drive_file("wxyz", later)).unwrap());
+    }
+}
@@ -13,11 +13,11 @@ use crate::{cli::AuthMethod, types::TDriveHub};
 pub async fn get_drive_client(auth_method: &AuthMethod) -> Result<TDriveHub> {
     match auth_method {
         AuthMethod::OAuth => {
-            println!("Using OAuth");
+            eprintln!("Using OAuth");
             get_oauth_client().await
         }
         AuthMethod::ServiceAccount => {
-            println!("Using service account");
+            eprintln!("Using service account");
             get_service_account_client().await
         }
     }
@@ -25,21 +25,21 @@ pub async fn get_drive_client(auth_method: &AuthMethod) -> Result<TDriveHub> {
 
 pub async fn get_service_account_client() -> Result<TDriveHub> {
     let sa_key_path = Path::new("service_account.json");
-    println!("Looking for service account file at: {:?}", sa_key_path);
-    println!("File exists: {}", sa_key_path.exists());
-    println!("Reading service account key...");
+    eprintln!("Looking for service account file at: {:?}", sa_key_path);
+    eprintln!("File exists: {}", sa_key_path.exists());
+    eprintln!("Reading service account key...");
     let sa_key = match yup_oauth2::read_service_account_key(sa_key_path).await {
         Ok(key) => {
-            println!("Successfully loaded service account key");
+            eprintln!("Successfully loaded service account key");
             key
         }
         Err(e) => {
-            println!("Failed to load service account key: {}", e);
+            eprintln!("Failed to load service account key: {}", e);
             return Err(e.into());
         }
     };
     let auth = ServiceAccountAuthenticator::builder(sa_key).build().await?;
-    println!("Authentication setup complete");
+    eprintln!("Authentication setup complete");
     let https = hyper_rustls::HttpsConnectorBuilder::new()
         .with_native_roots()
         .expect("no native root CA certificates found")
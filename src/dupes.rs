@@ -0,0 +1,222 @@
+use crate::{
+    cli::KeepRule,
+    output::format_size,
+    trash::trash_file,
+    types::TDriveHub,
+    utils::{
+        FILE_FIELDS, FOLDER_MIME_TYPE, confirm, drive_names, paths_from_listings, walk_tree,
+        with_retry,
+    },
+};
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt, future::ready};
+use google_drive3::api::File;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::atomic::AtomicUsize,
+};
+
+/// Reports groups of files below `folder_id` with identical content (same
+/// `md5Checksum` and size, and same name with `by_name`). Files without a
+/// checksum, such as Google Docs, are never considered duplicates. With
+/// `trash`, every copy but the one picked by `keep` is moved to the trash,
+/// after a confirmation unless `yes` is set.
+pub async fn find_dupes(
+    hub: TDriveHub,
+    folder_id: &str,
+    by_name: bool,
+    trash: bool,
+    keep: &KeepRule,
+    yes: bool,
+    concurrency: usize,
+) -> Result<()> {
+    eprintln!("Scanning folder {} for duplicates", folder_id);
+    let listings = walk_tree(&hub, folder_id, FILE_FIELDS, None, None, concurrency, None);
+    let files: Vec<(File, String)> = paths_from_listings(listings, folder_id, drive_names)
+        .try_filter_map(|item| {
+            let is_file = item.file.mime_type.as_deref() != Some(FOLDER_MIME_TYPE);
+            ready(Ok(is_file.then_some((item.file, item.path))))
+        })
+        .try_collect()
+        .await?;
+
+    let groups = duplicate_groups(files, by_name, keep);
+    if groups.is_empty() {
+        println!("No duplicates found");
+        return Ok(());
+    }
+
+    let mut redundant = Vec::new();
+    let mut wasted: u64 = 0;
+    for group in &groups {
+        let size = group[0].0.size.unwrap_or(0).max(0) as u64;
+        wasted += size * (group.len() as u64 - 1);
+
+        println!(
+            "\n{} copies of {} (md5 {})",
+            group.len(),
+            format_size(size),
+            group[0].0.md5_checksum.as_deref().unwrap_or_default()
+        );
+        for (i, (file, path)) in group.iter().enumerate() {
+            let created = file
+                .created_time
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default();
+            let mark = if i == 0 { "keep" } else { "dupe" };
+            println!(
+                "  {}  {}  {}  {}",
+                mark,
+                file.id.as_deref().unwrap_or_default(),
+                created,
+                path
+            );
+            if i > 0 {
+                redundant.push((file.id.clone().unwrap_or_default(), path.clone()));
+            }
+        }
+    }
+
+    eprintln!("\nDuplicate Summary:");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("Groups: {}", groups.len());
+    eprintln!("Redundant copies: {}", redundant.len());
+    eprintln!("Wasted space: {}", format_size(wasted));
+
+    if !trash {
+        return Ok(());
+    }
+    let question = format!(
+        "Move {} redundant copies ({}) to the trash",
+        redundant.len(),
+        format_size(wasted)
+    );
+    if !yes && !confirm(&question, false)? {
+        eprintln!("Nothing trashed");
+        return Ok(());
+    }
+
+    let retry_count = AtomicUsize::new(0);
+    let failed = futures::stream::iter(&redundant)
+        .map(|(id, path)| {
+            let hub = &hub;
+            let retries = &retry_count;
+            async move {
+                let result = with_retry("Trash", path, 3, retries, || trash_file(hub, id)).await;
+                match &result {
+                    Ok(_) => eprintln!("🗑 Trashed '{}'", path),
+                    Err(e) => eprintln!("✗ Failed to trash '{}': {}", path, e),
+                }
+                result.is_err()
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .filter(|failed| ready(*failed))
+        .count()
+        .await;
+
+    eprintln!(
+        "Trashed {} of {} redundant copies",
+        redundant.len() - failed,
+        redundant.len()
+    );
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} files could not be trashed", failed));
+    }
+    Ok(())
+}
+
+/// Groups `(file, path)` pairs by content (`md5Checksum` and size, plus the
+/// name with `by_name`) and returns the groups with more than one file, each
+/// ordered so the copy to keep comes first. Files without a checksum, and
+/// files listed twice because they have several parents, count once or not
+/// at all.
+fn duplicate_groups(
+    files: Vec<(File, String)>,
+    by_name: bool,
+    keep: &KeepRule,
+) -> Vec<Vec<(File, String)>> {
+    let mut seen = HashSet::new();
+    // (md5, size, name) -> files with their paths
+    let mut groups = BTreeMap::new();
+    for (file, path) in files {
+        if let (Some(md5), Some(size)) = (&file.md5_checksum, file.size)
+            && seen.insert(file.id.clone().unwrap_or_default())
+        {
+            let name = by_name.then(|| file.name.clone().unwrap_or_default());
+            groups
+                .entry((md5.clone(), size, name))
+                .or_insert_with(Vec::new)
+                .push((file, path));
+        }
+    }
+
+    let mut groups: Vec<_> = groups.into_values().filter(|g| g.len() > 1).collect();
+    for group in &mut groups {
+        group.sort_by(|(a, _), (b, _)| (a.created_time, &a.id).cmp(&(b.created_time, &b.id)));
+        if let KeepRule::Newest = keep {
+            group.reverse();
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn file(id: &str, name: &str, md5: Option<&str>, size: i64, created: i64) -> (File, String) {
+        let file = File {
+            id: Some(id.to_string()),
+            name: Some(name.to_string()),
+            md5_checksum: md5.map(str::to_string),
+            size: Some(size),
+            created_time: Utc.timestamp_opt(created, 0).single(),
+            ..Default::default()
+        };
+        (file, format!("dir/{}", name))
+    }
+
+    fn ids(groups: &[Vec<(File, String)>]) -> Vec<Vec<&str>> {
+        groups
+            .iter()
+            .map(|g| g.iter().map(|(f, _)| f.id.as_deref().unwrap()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn groups_by_content_and_keeps_the_oldest_first() {
+        let files = vec![
+            file("b", "copy.pdf", Some("aa"), 10, 200),
+            file("a", "orig.pdf", Some("aa"), 10, 100),
+            file("c", "other.pdf", Some("bb"), 10, 100),
+            file("d", "doc", None, 10, 100),
+            file("e", "doc", None, 10, 100),
+        ];
+        let groups = duplicate_groups(files, false, &KeepRule::Oldest);
+        assert_eq!(ids(&groups), vec![vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn newest_rule_and_by_name() {
+        let files = vec![
+            file("a", "x.pdf", Some("aa"), 10, 100),
+            file("b", "x.pdf", Some("aa"), 10, 200),
+            file("c", "y.pdf", Some("aa"), 10, 300),
+        ];
+        let groups = duplicate_groups(files.clone(), false, &KeepRule::Newest);
+        assert_eq!(ids(&groups), vec![vec!["c", "b", "a"]]);
+        let groups = duplicate_groups(files, true, &KeepRule::Oldest);
+        assert_eq!(ids(&groups), vec![vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn a_file_in_several_folders_is_not_its_own_duplicate() {
+        let files = vec![
+            file("a", "x.pdf", Some("aa"), 10, 100),
+            file("a", "x.pdf", Some("aa"), 10, 100),
+        ];
+        assert!(duplicate_groups(files, false, &KeepRule::Oldest).is_empty());
+    }
+}
@@ -0,0 +1,732 @@
+use crate::{
+    cli::SourceArgs,
+    output::format_size,
+    types::TDriveHub,
+    utils::{
+        FILE_FIELDS, FOLDER_MIME_TYPE, escape_query, is_not_found, query_files,
+        stream_source_files, walk_tree, with_retry,
+    },
+};
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt, future::ready};
+use google_drive3::api::File;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+use tokio::sync::mpsc;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Copies `src` into the folder `dest` on the Drive side, without any data
+/// passing through this machine. A folder needs `recursive`, and `dest` must
+/// not be inside it: its structure is recreated below `dest` as it is read,
+/// and the files of each folder are copied, `concurrency` at a time, as soon
+/// as its copy exists. Names and descriptions are kept, custom properties
+/// only with `properties`.
+pub async fn copy_files(
+    hub: TDriveHub,
+    src: &str,
+    dest: &str,
+    recursive: bool,
+    properties: bool,
+    concurrency: usize,
+) -> Result<()> {
+    let start_time = Instant::now();
+    let fields = "id, name, mimeType, size, description, properties, appProperties";
+    let (_, root) = hub
+        .files()
+        .get(src)
+        .supports_all_drives(true)
+        .param("fields", fields)
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    let root_name = root.name.clone().unwrap_or_default();
+    let is_folder = root.mime_type.as_deref() == Some(FOLDER_MIME_TYPE);
+    if is_folder && !recursive {
+        return Err(anyhow::anyhow!(
+            "'{}' is a folder, use -r to copy it with everything inside",
+            root_name
+        ));
+    }
+    // the walk would otherwise find its own copies and copy them again
+    if is_folder && is_within(&hub, dest, src).await? {
+        return Err(anyhow::anyhow!(
+            "Cannot copy '{}' into itself, pick a destination outside it",
+            root_name
+        ));
+    }
+    println!(
+        "Copying '{}' with up to {} files at a time",
+        root_name,
+        concurrency.max(1)
+    );
+
+    let retry_count = AtomicUsize::new(0);
+    // (file to copy, ID of the folder the copy goes into); files are copied
+    // while the rest of the tree is still being read
+    let (jobs, mut jobs_rx) = mpsc::channel::<(File, String)>(1000);
+    // returns how many folders were created and how many files were skipped
+    let walk = async {
+        let jobs = jobs;
+        if !is_folder {
+            let _ = jobs.send((root, dest.to_string())).await;
+            return Ok((0, 0));
+        }
+        let mut retry = false;
+        let copy_root = with_retry("Create folder", &root_name, 3, &retry_count, || {
+            copy_folder(
+                &hub,
+                &root,
+                dest,
+                properties,
+                std::mem::replace(&mut retry, true),
+            )
+        })
+        .await?;
+        println!("Created folder '{}' -> ID: {}", root_name, copy_root);
+        let mut copies = HashMap::from([(src.to_string(), copy_root)]);
+        let mut skipped = 0;
+
+        // listings come parents first, so each folder's copy already exists
+        let mut listings = walk_tree(&hub, src, fields, None, None, concurrency, None);
+        while let Some(listing) = listings.try_next().await? {
+            let (subfolders, files): (Vec<File>, Vec<File>) = listing
+                .children
+                .into_iter()
+                .partition(|f| f.mime_type.as_deref() == Some(FOLDER_MIME_TYPE));
+            let Some(parent) = copies.get(&listing.folder_id).cloned() else {
+                skipped += files.len();
+                continue;
+            };
+            let created: Vec<_> = futures::stream::iter(subfolders)
+                .map(|folder| {
+                    let hub = &hub;
+                    let parent = &parent;
+                    let retries = &retry_count;
+                    async move {
+                        let name = folder.name.as_deref().unwrap_or_default();
+                        let mut retry = false;
+                        let result = with_retry("Create folder", name, 3, retries, || {
+                            let retry = std::mem::replace(&mut retry, true);
+                            copy_folder(hub, &folder, parent, properties, retry)
+                        })
+                        .await;
+                        (folder, result)
+                    }
+                })
+                .buffer_unordered(concurrency.max(1))
+                .collect()
+                .await;
+            for (folder, result) in created {
+                let id = folder.id.clone().unwrap_or_default();
+                match result {
+                    Ok(copy_id) => {
+                        copies.entry(id).or_insert(copy_id);
+                    }
+                    Err(e) => eprintln!(
+                        "✗ Failed to create folder '{}', skipping its contents: {}",
+                        folder.name.as_deref().unwrap_or_default(),
+                        e
+                    ),
+                }
+            }
+            for file in files {
+                if jobs.send((file, parent.clone())).await.is_err() {
+                    break;
+                }
+            }
+        }
+        Ok::<_, anyhow::Error>((copies.len(), skipped))
+    };
+
+    let overall_progress = ProgressBar::new(0);
+    overall_progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Copying [{bar:40.cyan/blue}] {pos}/{len} files ({percent}%) | {elapsed_precise} | ETA: {eta_precise}")
+            .unwrap()
+            .progress_chars("█▓▒░ ")
+    );
+    let copy = futures::stream::poll_fn(|cx| jobs_rx.poll_recv(cx))
+        .map(|(file, parent)| {
+            let hub = &hub;
+            let retries = &retry_count;
+            let overall_prog = &overall_progress;
+            overall_prog.inc_length(1);
+            async move {
+                let name = file.name.as_deref().unwrap_or_default();
+                let mut retry = false;
+                let result = with_retry("Copy", name, 3, retries, || {
+                    let retry = std::mem::replace(&mut retry, true);
+                    copy_file(hub, &file, &parent, properties, retry)
+                })
+                .await;
+                overall_prog.inc(1);
+                overall_prog.set_message(name.to_string());
+                if let Err(e) = &result {
+                    overall_prog.suspend(|| eprintln!("✗ Failed '{}': {}", name, e));
+                }
+                (result.is_ok(), file.size.unwrap_or(0).max(0) as u64)
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .fold((0, 0, 0), |(copied, failed, bytes), (ok, size)| {
+            ready(match ok {
+                true => (copied + 1, failed, bytes + size),
+                false => (copied, failed + 1, bytes),
+            })
+        });
+    let (walked, (copied, failed, total_bytes)) = tokio::join!(walk, copy);
+    overall_progress.finish_with_message("Copy complete!");
+    let (folders, skipped) = walked.as_ref().map_or((0, 0), |w| *w);
+
+    println!("\nCopy Summary:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    if folders > 0 {
+        println!("Created folders: {}", folders);
+    }
+    println!("Copied files: {}", copied);
+    println!("Failed files: {}", failed);
+    if skipped > 0 {
+        println!("Skipped (folder not created): {}", skipped);
+    }
+    println!("Total size copied: {}", format_size(total_bytes));
+    println!("Total retries: {}", retry_count.load(Ordering::Relaxed));
+    println!("Total time: {:?}", start_time.elapsed());
+
+    if let Err(e) = walked {
+        return Err(e.context("Listing stopped early, some files were not copied"));
+    }
+    if failed + skipped > 0 {
+        return Err(anyhow::anyhow!(
+            "{} files were not copied",
+            failed + skipped
+        ));
+    }
+    Ok(())
+}
+
+/// Creates the folder at `path` below `parent` and prints its ID. Without
+/// `parents` every folder but the last has to exist and the last must not;
+/// with `parents` missing folders are created and existing ones reused.
+pub async fn make_dir(hub: TDriveHub, path: &str, parent: &str, parents: bool) -> Result<()> {
+    let names: Vec<&str> = path.split('/').filter(|n| !n.is_empty()).collect();
+    let Some((last, ancestors)) = names.split_last() else {
+        return Err(anyhow::anyhow!("No folder name given"));
+    };
+
+    let mut folder_id = parent.to_string();
+    for name in ancestors {
+        folder_id = match parents {
+            true => ensure_folder(&hub, &folder_id, name).await?.0,
+            false => find_folder(&hub, &folder_id, name).await?.ok_or_else(|| {
+                anyhow::anyhow!("Folder '{}' does not exist, use -p to create it", name)
+            })?,
+        };
+    }
+    let (id, created) = ensure_folder(&hub, &folder_id, last).await?;
+    if !created && !parents {
+        return Err(anyhow::anyhow!("Folder '{}' already exists ({})", path, id));
+    }
+    println!("{}", id);
+    Ok(())
+}
+
+/// Creates the folder tree described by the YAML file `spec` below `parent`
+/// and prints a JSON object mapping each folder path to its ID. The spec
+/// nests folder names as mapping keys; a list holds folders without
+/// subfolders, and an empty value ends a branch:
+///
+/// ```yaml
+/// Client:
+///   Contracts:
+///   Invoices: [2025, 2026]
+/// ```
+///
+/// Folders that already exist are reused, so running it again is harmless.
+pub async fn provision(hub: TDriveHub, spec: &str, parent: &str) -> Result<()> {
+    let spec = parse_spec(&std::fs::read_to_string(spec)?)?;
+    let mut ids = serde_json::Map::new();
+    let (mut created, mut existing) = (0, 0);
+    // (folders to create, folder they go in, path of that folder)
+    let mut pending = vec![(spec.as_slice(), parent.to_string(), String::new())];
+
+    while let Some((children, folder_id, path)) = pending.pop() {
+        for child in children {
+            let (id, is_new) = ensure_folder(&hub, &folder_id, &child.name).await?;
+            let child_path = match path.is_empty() {
+                true => child.name.clone(),
+                false => format!("{}/{}", path, child.name),
+            };
+            eprintln!(
+                "{} '{}' -> ID: {}",
+                if is_new { "Created" } else { "Exists " },
+                child_path,
+                id
+            );
+            if is_new {
+                created += 1;
+            } else {
+                existing += 1;
+            }
+            ids.insert(child_path.clone(), id.clone().into());
+            if !child.children.is_empty() {
+                pending.push((&child.children, id, child_path));
+            }
+        }
+    }
+
+    // sorted by path, so every folder comes right before its subfolders
+    let mut ids: Vec<_> = ids.into_iter().collect();
+    ids.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let ids: serde_json::Map<_, _> = ids.into_iter().collect();
+    println!("{}", serde_json::to_string_pretty(&ids)?);
+    eprintln!("Created {} folders, {} already existed", created, existing);
+    Ok(())
+}
+
+/// A folder of a `provision` spec and the folders to create inside it.
+#[derive(Debug, PartialEq)]
+struct SpecFolder {
+    name: String,
+    children: Vec<SpecFolder>,
+}
+
+/// Reads the folders of a `provision` spec; an empty file describes none.
+fn parse_spec(text: &str) -> Result<Vec<SpecFolder>> {
+    let docs = YamlLoader::load_from_str(text).context("Invalid folder spec")?;
+    match docs.first() {
+        Some(doc) => spec_folders(doc),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The folders a spec node describes: mapping keys with their own contents,
+/// list items or a single name without subfolders, or none for an empty value.
+fn spec_folders(node: &Yaml) -> Result<Vec<SpecFolder>> {
+    let leaf = |name: &Yaml| {
+        Ok(SpecFolder {
+            name: yaml_name(name)?,
+            children: Vec::new(),
+        })
+    };
+    match node {
+        Yaml::Null => Ok(Vec::new()),
+        Yaml::Hash(map) => map
+            .iter()
+            .map(|(name, node)| {
+                Ok(SpecFolder {
+                    name: yaml_name(name)?,
+                    children: spec_folders(node)?,
+                })
+            })
+            .collect(),
+        Yaml::Array(items) => items.iter().map(leaf).collect(),
+        other => Ok(vec![leaf(other)?]),
+    }
+}
+
+/// Turns a YAML key or list item into a folder name.
+fn yaml_name(value: &Yaml) -> Result<String> {
+    match value {
+        Yaml::String(s) | Yaml::Real(s) => Ok(s.clone()),
+        Yaml::Integer(n) => Ok(n.to_string()),
+        Yaml::Boolean(b) => Ok(b.to_string()),
+        other => Err(anyhow::anyhow!(
+            "Expected a folder name in the spec, got {:?}",
+            other
+        )),
+    }
+}
+
+/// Looks up a folder called `name` directly inside `parent`.
+async fn find_folder(hub: &TDriveHub, parent: &str, name: &str) -> Result<Option<String>> {
+    let query = format!(
+        "'{}' in parents and name = '{}' and mimeType = '{}' and trashed=false",
+        parent,
+        escape_query(name),
+        FOLDER_MIME_TYPE
+    );
+    let found: Vec<File> = query_files(Arc::clone(hub), &query, "id", Some("createdTime"), false)
+        .try_collect()
+        .await?;
+    if found.len() > 1 {
+        eprintln!(
+            "{} folders called '{}' in {}, using the oldest",
+            found.len(),
+            name,
+            parent
+        );
+    }
+    Ok(found.into_iter().next().and_then(|f| f.id))
+}
+
+/// Returns the ID of the folder `name` inside `parent`, creating it if
+/// needed, and whether it was created.
+async fn ensure_folder(hub: &TDriveHub, parent: &str, name: &str) -> Result<(String, bool)> {
+    if let Some(id) = find_folder(hub, parent, name).await? {
+        return Ok((id, false));
+    }
+    let template = File {
+        name: Some(name.to_string()),
+        ..Default::default()
+    };
+    Ok((create_folder(hub, template, parent).await?, true))
+}
+
+/// Creates a folder inside `parent` with the name, description and
+/// properties of `template`, and returns the new ID.
+async fn create_folder(hub: &TDriveHub, template: File, parent: &str) -> Result<String> {
+    let folder = File {
+        mime_type: Some(FOLDER_MIME_TYPE.to_string()),
+        parents: Some(vec![parent.to_string()]),
+        ..template
+    };
+    let mime: mime::Mime = FOLDER_MIME_TYPE.parse()?;
+    // folders have no content, but the client only creates files by upload
+    let (_, created) = hub
+        .files()
+        .create(folder)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .upload(std::io::Cursor::new(Vec::new()), mime)
+        .await?;
+    Ok(created.id.unwrap_or_default())
+}
+
+/// App property that tags every copy with the ID of its source, so a retry
+/// can tell whether an attempt whose answer got lost went through.
+const COPIED_FROM: &str = "copiedFrom";
+
+/// The metadata a copy of `file` gets: its name and description, custom
+/// properties only with `properties`, and the [`COPIED_FROM`] tag.
+fn copy_template(file: &File, properties: bool) -> File {
+    let mut app_properties = file
+        .app_properties
+        .clone()
+        .filter(|_| properties)
+        .unwrap_or_default();
+    app_properties.insert(COPIED_FROM.to_string(), file.id.clone().unwrap_or_default());
+    File {
+        name: file.name.clone(),
+        description: file.description.clone(),
+        properties: file.properties.clone().filter(|_| properties),
+        app_properties: Some(app_properties),
+        ..Default::default()
+    }
+}
+
+/// The ID of a copy of `source_id` an earlier attempt made in `parent`.
+async fn find_copy(hub: &TDriveHub, source_id: &str, parent: &str) -> Result<Option<String>> {
+    let query = format!(
+        "'{}' in parents and appProperties has {{ key='{}' and value='{}' }} and trashed=false",
+        parent,
+        COPIED_FROM,
+        escape_query(source_id)
+    );
+    let found = query_files(Arc::clone(hub), &query, "id", None, false)
+        .try_next()
+        .await?;
+    Ok(found.and_then(|f| f.id))
+}
+
+/// Recreates `folder` inside `parent` and returns the ID of the copy. A
+/// `retry` first looks for a copy the previous attempt may have made.
+async fn copy_folder(
+    hub: &TDriveHub,
+    folder: &File,
+    parent: &str,
+    properties: bool,
+    retry: bool,
+) -> Result<String> {
+    let source_id = folder.id.as_deref().unwrap_or_default();
+    if retry && let Some(id) = find_copy(hub, source_id, parent).await? {
+        return Ok(id);
+    }
+    create_folder(hub, copy_template(folder, properties), parent).await
+}
+
+/// Copies one file into `parent` under its own name and description. A
+/// `retry` does nothing if the previous attempt's copy is already there, as
+/// blindly copying again could leave two copies.
+async fn copy_file(
+    hub: &TDriveHub,
+    file: &File,
+    parent: &str,
+    properties: bool,
+    retry: bool,
+) -> Result<()> {
+    let source_id = file.id.as_deref().unwrap_or_default();
+    if retry && find_copy(hub, source_id, parent).await?.is_some() {
+        return Ok(());
+    }
+    let copy = File {
+        parents: Some(vec![parent.to_string()]),
+        ..copy_template(file, properties)
+    };
+    hub.files()
+        .copy(copy, source_id)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    Ok(())
+}
+
+/// Whether `folder_id` is `ancestor_id` or somewhere below it, following
+/// every parent up to the top of what this account can see.
+async fn is_within(hub: &TDriveHub, folder_id: &str, ancestor_id: &str) -> Result<bool> {
+    let mut seen = HashSet::new();
+    let mut pending = vec![folder_id.to_string()];
+    while let Some(id) = pending.pop() {
+        if id == ancestor_id {
+            return Ok(true);
+        }
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let result = hub
+            .files()
+            .get(&id)
+            .supports_all_drives(true)
+            .param("fields", "parents")
+            .add_scope(google_drive3::api::Scope::Full)
+            .doit()
+            .await;
+        match result {
+            Ok((_, folder)) => pending.extend(folder.parents.unwrap_or_default()),
+            // a parent this account cannot see is as far up as it goes
+            Err(e) if id != folder_id && is_not_found(&e) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(false)
+}
+
+/// Moves the selected files into the folder `to`. Files picked from a folder
+/// leave only that folder and keep any other parents they have; files picked
+/// by query or ID leave all their current parents. With `dry_run` the moves
+/// are only printed.
+pub async fn move_files(
+    hub: TDriveHub,
+    source: &SourceArgs,
+    to: &str,
+    dry_run: bool,
+    max_concurrency: usize,
+) -> Result<()> {
+    let start_time = Instant::now();
+    let mut already_there = 0;
+    let fields = format!("{}, parents", FILE_FIELDS);
+    // (file, parents it leaves); moves start while the rest is still listed
+    let moves =
+        stream_source_files(&hub, source, &fields, max_concurrency, None)?.try_filter_map(|file| {
+            let parents = file.parents.clone().unwrap_or_default();
+            let leaving: Vec<String> = match &source.folder_id {
+                Some(folder_id) => vec![folder_id.clone()],
+                None => parents.clone(),
+            }
+            .into_iter()
+            .filter(|p| p != to)
+            .collect();
+            if leaving.is_empty() && parents.iter().any(|p| p == to) {
+                already_there += 1;
+                return ready(Ok(None));
+            }
+            ready(Ok(Some((file, leaving))))
+        });
+
+    if dry_run {
+        let mut moves = moves;
+        let mut count = 0;
+        while let Some((file, leaving)) = moves.try_next().await? {
+            println!(
+                "Would move '{}' ({}): {} -> {}",
+                file.name.as_deref().unwrap_or_default(),
+                file.id.as_deref().unwrap_or_default(),
+                leaving.join(", "),
+                to
+            );
+            count += 1;
+        }
+        drop(moves);
+        println!(
+            "\nDry run: {} files would be moved, {} already in {}",
+            count, already_there, to
+        );
+        return Ok(());
+    }
+
+    let overall_progress = ProgressBar::new(0);
+    overall_progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Moving [{bar:40.cyan/blue}] {pos}/{len} files ({percent}%) | {elapsed_precise} | ETA: {eta_precise}")
+            .unwrap()
+            .progress_chars("█▓▒░ ")
+    );
+    let retry_count = AtomicUsize::new(0);
+    let mut listing_error = None;
+    let (moved, failed) = moves
+        // a listing error ends the stream and is returned after the moves
+        .scan(&mut listing_error, |error, result| {
+            ready(match result {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    **error = Some(e);
+                    None
+                }
+            })
+        })
+        .map(|(file, leaving)| {
+            let hub = &hub;
+            let retries = &retry_count;
+            let overall_prog = &overall_progress;
+            overall_prog.inc_length(1);
+            async move {
+                let id = file.id.as_deref().unwrap_or_default();
+                let name = file.name.as_deref().unwrap_or_default();
+                let result = with_retry("Move", name, 3, retries, || {
+                    move_file(hub, id, to, &leaving)
+                })
+                .await;
+                overall_prog.inc(1);
+                overall_prog.set_message(name.to_string());
+                if let Err(e) = &result {
+                    overall_prog.suspend(|| eprintln!("✗ Failed '{}' ({}): {}", name, id, e));
+                }
+                result.is_ok()
+            }
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .fold((0, 0), |(moved, failed), ok| {
+            ready(match ok {
+                true => (moved + 1, failed),
+                false => (moved, failed + 1),
+            })
+        })
+        .await;
+    overall_progress.finish_with_message("Move complete!");
+
+    if moved + failed == 0 && listing_error.is_none() {
+        println!("Nothing to move, {} files already in {}", already_there, to);
+        return Ok(());
+    }
+
+    println!("\nMove Summary:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("Moved: {}", moved);
+    println!("Failed: {}", failed);
+    println!("Already in place: {}", already_there);
+    println!("Total retries: {}", retry_count.load(Ordering::Relaxed));
+    println!("Total time: {:?}", start_time.elapsed());
+
+    if let Some(e) = listing_error {
+        return Err(e.context("Listing stopped early, some files were not moved"));
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} files could not be moved", failed));
+    }
+    Ok(())
+}
+
+/// Adds `to` as a parent of a file and removes the `leaving` parents in one
+/// request.
+async fn move_file(hub: &TDriveHub, file_id: &str, to: &str, leaving: &[String]) -> Result<()> {
+    let mut request = hub
+        .files()
+        .update(File::default(), file_id)
+        .add_parents(to)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full);
+    if !leaving.is_empty() {
+        request = request.remove_parents(&leaving.join(","));
+    }
+    request.doit_without_upload().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_template_tags_the_source_and_drops_properties() {
+        let file = File {
+            id: Some("src1".to_string()),
+            name: Some("report.pdf".to_string()),
+            description: Some("Q1".to_string()),
+            properties: Some(HashMap::from([("k".to_string(), "v".to_string())])),
+            app_properties: Some(HashMap::from([("a".to_string(), "b".to_string())])),
+            size: Some(10),
+            ..Default::default()
+        };
+
+        let copy = copy_template(&file, false);
+        assert_eq!(copy.name.as_deref(), Some("report.pdf"));
+        assert_eq!(copy.description.as_deref(), Some("Q1"));
+        assert_eq!(copy.properties, None);
+        assert_eq!(
+            copy.app_properties,
+            Some(HashMap::from([(
+                COPIED_FROM.to_string(),
+                "src1".to_string()
+            )]))
+        );
+        assert_eq!(copy.size, None);
+
+        let copy = copy_template(&file, true);
+        assert_eq!(copy.properties, file.properties);
+        assert_eq!(copy.app_properties.unwrap().len(), 2);
+    }
+
+    fn folder(name: &str, children: Vec<SpecFolder>) -> SpecFolder {
+        SpecFolder {
+            name: name.to_string(),
+            children,
+        }
+    }
+
+    #[test]
+    fn parse_spec_nests_mappings_and_ends_at_empty_values() {
+        let spec = "Client:\n  Contracts:\n  Archive: ~\n  Drafts:\n    Old:\n";
+        assert_eq!(
+            parse_spec(spec).unwrap(),
+            vec![folder(
+                "Client",
+                vec![
+                    folder("Contracts", vec![]),
+                    folder("Archive", vec![]),
+                    folder("Drafts", vec![folder("Old", vec![])]),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_spec_reads_lists_and_numeric_names() {
+        let spec = "Invoices: [2025, 2026]\nReports:\n  - Q1\n  - 1.5\nNotes: Todo\n2026:\n";
+        assert_eq!(
+            parse_spec(spec).unwrap(),
+            vec![
+                folder(
+                    "Invoices",
+                    vec![folder("2025", vec![]), folder("2026", vec![])]
+                ),
+                folder("Reports", vec![folder("Q1", vec![]), folder("1.5", vec![])]),
+                folder("Notes", vec![folder("Todo", vec![])]),
+                folder("2026", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_spec_rejects_what_is_not_a_folder_name() {
+        assert_eq!(parse_spec("").unwrap(), vec![]);
+        assert!(parse_spec("Client: [[2025]]").is_err());
+        assert!(parse_spec("Client: [").is_err());
+    }
+}
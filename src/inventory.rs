@@ -0,0 +1,470 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{TryStreamExt, future::ready};
+use google_drive3::api::File;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, atomic::AtomicUsize},
+    time::Instant,
+};
+
+use crate::{
+    types::{TDriveHub, TreeItem},
+    utils::{FILE_FIELDS, drive_names, paths_from_listings, query_files, walk_tree, with_retry},
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    id TEXT PRIMARY KEY,
+    root_id TEXT NOT NULL,
+    parent_id TEXT,
+    path TEXT NOT NULL,
+    name TEXT NOT NULL,
+    mime_type TEXT,
+    size INTEGER,
+    md5_checksum TEXT,
+    sha1_checksum TEXT,
+    sha256_checksum TEXT,
+    created_time TEXT,
+    modified_time TEXT,
+    shared INTEGER,
+    web_view_link TEXT,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    removed_time TEXT
+);
+CREATE INDEX IF NOT EXISTS files_path ON files (path);
+CREATE INDEX IF NOT EXISTS files_md5 ON files (md5_checksum);
+CREATE TABLE IF NOT EXISTS owners (
+    file_id TEXT NOT NULL REFERENCES files (id),
+    email_address TEXT NOT NULL,
+    display_name TEXT,
+    PRIMARY KEY (file_id, email_address)
+);
+CREATE TABLE IF NOT EXISTS permissions (
+    file_id TEXT NOT NULL REFERENCES files (id),
+    permission_id TEXT NOT NULL,
+    type TEXT,
+    role TEXT,
+    email_address TEXT,
+    domain TEXT,
+    PRIMARY KEY (file_id, permission_id)
+);
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    root_id TEXT NOT NULL,
+    started_time TEXT NOT NULL,
+    finished_time TEXT NOT NULL,
+    file_count INTEGER NOT NULL
+);
+";
+
+/// A SQLite copy of Drive metadata with one row per file in `files`, plus
+/// `owners` and `permissions` per file and one `runs` row per export. Rows
+/// are updated in place by later runs; files that disappeared keep their row
+/// with `removed_time` set.
+pub struct Inventory {
+    conn: Connection,
+    /// the run in progress: its root folder, start time and counts
+    root_id: String,
+    started: String,
+    stats: InventoryStats,
+}
+
+/// What one run changed in the database.
+#[derive(Default)]
+pub struct InventoryStats {
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+impl Inventory {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn,
+            root_id: String::new(),
+            started: String::new(),
+            stats: InventoryStats::default(),
+        })
+    }
+
+    /// When the last finished run from `root_id` started, if there was one.
+    pub fn last_run(&self, root_id: &str) -> Result<Option<String>> {
+        Ok(self.conn.query_row(
+            "SELECT MAX(started_time) FROM runs WHERE root_id = ?1",
+            [root_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Starts a run from `root_id`. Nothing is committed until
+    /// [`Inventory::finish`], so a run that fails part way changes nothing.
+    pub fn begin(&mut self, root_id: &str) -> Result<()> {
+        self.conn.execute_batch("BEGIN")?;
+        self.root_id = root_id.to_string();
+        self.started = timestamp(Some(Utc::now())).unwrap_or_default();
+        self.stats = InventoryStats::default();
+        Ok(())
+    }
+
+    /// Records a file with all of its metadata, under its path relative to
+    /// the run's root.
+    pub fn record(&mut self, item: &TreeItem) -> Result<()> {
+        let TreeItem {
+            file,
+            path,
+            parent_id,
+        } = item;
+        let id = file.id.as_deref().unwrap_or_default();
+        let modified = timestamp(file.modified_time);
+        let previous: Option<Option<String>> = self
+            .conn
+            .query_row(
+                "SELECT modified_time FROM files WHERE id = ?1 AND removed_time IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match previous {
+            None => self.stats.added += 1,
+            Some(previous) if previous != modified => self.stats.changed += 1,
+            Some(_) => self.stats.unchanged += 1,
+        }
+
+        self.conn.execute(
+            "INSERT INTO files (id, root_id, parent_id, path, name, mime_type, size,
+                md5_checksum, sha1_checksum, sha256_checksum, created_time, modified_time,
+                shared, web_view_link, first_seen, last_seen, removed_time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?15, NULL)
+             ON CONFLICT (id) DO UPDATE SET
+                root_id = excluded.root_id, parent_id = excluded.parent_id,
+                path = excluded.path, name = excluded.name,
+                mime_type = excluded.mime_type, size = excluded.size,
+                md5_checksum = excluded.md5_checksum,
+                sha1_checksum = excluded.sha1_checksum,
+                sha256_checksum = excluded.sha256_checksum,
+                created_time = excluded.created_time,
+                modified_time = excluded.modified_time, shared = excluded.shared,
+                web_view_link = excluded.web_view_link,
+                last_seen = excluded.last_seen, removed_time = NULL",
+            params![
+                id,
+                self.root_id,
+                parent_id,
+                path,
+                file.name.as_deref().unwrap_or_default(),
+                file.mime_type,
+                file.size,
+                file.md5_checksum,
+                file.sha1_checksum,
+                file.sha256_checksum,
+                timestamp(file.created_time),
+                modified,
+                file.shared,
+                file.web_view_link,
+                self.started,
+            ],
+        )?;
+
+        self.conn
+            .execute("DELETE FROM owners WHERE file_id = ?1", [id])?;
+        for owner in file.owners.iter().flatten() {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO owners (file_id, email_address, display_name)
+                 VALUES (?1, ?2, ?3)",
+                params![
+                    id,
+                    owner.email_address.as_deref().unwrap_or_default(),
+                    owner.display_name
+                ],
+            )?;
+        }
+
+        self.conn
+            .execute("DELETE FROM permissions WHERE file_id = ?1", [id])?;
+        for permission in file.permissions.iter().flatten() {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO permissions
+                    (file_id, permission_id, type, role, email_address, domain)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    id,
+                    permission.id.as_deref().unwrap_or_default(),
+                    permission.type_,
+                    permission.role,
+                    permission.email_address,
+                    permission.domain
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Marks a file the inventory already holds as still there, updating only
+    /// where it is and what it is called. Returns false when the file is not
+    /// in the inventory, so its metadata has to be recorded in full.
+    pub fn touch(&mut self, item: &TreeItem) -> Result<bool> {
+        let updated = self.conn.execute(
+            "UPDATE files SET root_id = ?2, parent_id = ?3, path = ?4, name = ?5,
+                last_seen = ?6
+             WHERE id = ?1 AND removed_time IS NULL",
+            params![
+                item.file.id.as_deref().unwrap_or_default(),
+                self.root_id,
+                item.parent_id,
+                item.path,
+                item.file.name.as_deref().unwrap_or_default(),
+                self.started,
+            ],
+        )?;
+        if updated > 0 {
+            self.stats.unchanged += 1;
+        }
+        Ok(updated > 0)
+    }
+
+    /// Marks the files of the run's root that were not seen by this run as
+    /// removed, logs the run and commits it.
+    pub fn finish(&mut self) -> Result<InventoryStats> {
+        let now = timestamp(Some(Utc::now())).unwrap_or_default();
+        self.stats.removed = self.conn.execute(
+            "UPDATE files SET removed_time = ?1
+             WHERE root_id = ?2 AND last_seen < ?1 AND removed_time IS NULL",
+            params![self.started, self.root_id],
+        )?;
+        let stats = std::mem::take(&mut self.stats);
+        self.conn.execute(
+            "INSERT INTO runs (root_id, started_time, finished_time, file_count)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.root_id,
+                self.started,
+                now,
+                (stats.added + stats.changed + stats.unchanged) as i64
+            ],
+        )?;
+        self.conn.execute_batch("COMMIT")?;
+        Ok(stats)
+    }
+}
+
+/// Stores times in one fixed RFC 3339 form, so they compare correctly as text.
+fn timestamp(time: Option<DateTime<Utc>>) -> Option<String> {
+    time.map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+/// Walks `folder_id` and records every file and folder below it in the
+/// SQLite database at `db`. The first run, and every run with `full`, reads
+/// all metadata of the whole tree. Later runs fetch full metadata only for
+/// files modified since the previous run started and for files the inventory
+/// does not hold yet; the rest of the tree is listed by name and type alone,
+/// to update paths and find removed files. Rows are written as the walk goes.
+pub async fn export_inventory(
+    hub: TDriveHub,
+    folder_id: &str,
+    db: &str,
+    concurrency: usize,
+    full: bool,
+) -> Result<()> {
+    let start_time = Instant::now();
+    println!("Taking inventory of folder {}", folder_id);
+    // opened first, so a bad path fails before the walk
+    let mut inventory = Inventory::open(db)?;
+    let since = if full {
+        None
+    } else {
+        inventory.last_run(folder_id)?
+    };
+
+    let full_fields = format!(
+        "{}, sha1Checksum, sha256Checksum, owners(emailAddress, displayName), \
+         shared, webViewLink, permissions(id, type, role, emailAddress, domain)",
+        FILE_FIELDS
+    );
+    // everything modified since the last run, anywhere; only the files found
+    // in the tree below are kept
+    let mut changed = HashMap::new();
+    if let Some(since) = &since {
+        println!("Updating files changed since {}", since);
+        let query = format!("modifiedTime > '{}' and trashed=false", since);
+        let mut files = query_files(Arc::clone(&hub), &query, &full_fields, None, true);
+        while let Some(file) = files.try_next().await? {
+            changed.insert(file.id.clone().unwrap_or_default(), file);
+        }
+    }
+
+    let walk_fields = match since {
+        Some(_) => "id, name, mimeType",
+        None => &full_fields,
+    };
+    let listings = walk_tree(&hub, folder_id, walk_fields, None, None, concurrency, None);
+    let mut seen = HashSet::new();
+    let mut items = paths_from_listings(listings, folder_id, drive_names)
+        // items with several parents are recorded under the first path found
+        .try_filter(|item| ready(seen.insert(item.file.id.clone().unwrap_or_default())));
+
+    let retry_count = AtomicUsize::new(0);
+    let mut fetched = 0;
+    inventory.begin(folder_id)?;
+    while let Some(mut item) = items.try_next().await? {
+        if since.is_some() {
+            let id = item.file.id.clone().unwrap_or_default();
+            match changed.remove(&id) {
+                Some(file) => item.file = file,
+                None if inventory.touch(&item)? => continue,
+                // new to the inventory without a recent change, e.g. moved in
+                None => {
+                    item.file = with_retry("Look up", &item.path, 3, &retry_count, || {
+                        get_file(&hub, &id, &full_fields)
+                    })
+                    .await?;
+                    fetched += 1;
+                }
+            }
+        }
+        inventory.record(&item)?;
+    }
+    let stats = inventory.finish()?;
+
+    println!("\nInventory Summary:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("Database: {}", db);
+    println!(
+        "Run: {}",
+        if since.is_some() {
+            "incremental"
+        } else {
+            "full"
+        }
+    );
+    println!(
+        "Files and folders: {}",
+        stats.added + stats.changed + stats.unchanged
+    );
+    println!("New: {}", stats.added);
+    println!("Changed: {}", stats.changed);
+    println!("Unchanged: {}", stats.unchanged);
+    println!("Removed since last run: {}", stats.removed);
+    if since.is_some() {
+        println!("Looked up one by one: {}", fetched);
+    }
+    println!("Total time: {:?}", start_time.elapsed());
+    Ok(())
+}
+
+/// Fetches `fields` of one file.
+async fn get_file(hub: &TDriveHub, file_id: &str, fields: &str) -> Result<File> {
+    let (_, file) = hub
+        .files()
+        .get(file_id)
+        .supports_all_drives(true)
+        .param("fields", fields)
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, path: &str, modified: i64) -> TreeItem {
+        TreeItem {
+            file: File {
+                id: Some(id.to_string()),
+                name: path.rsplit('/').next().map(str::to_string),
+                modified_time: DateTime::from_timestamp(modified, 0),
+                ..Default::default()
+            },
+            path: path.to_string(),
+            parent_id: "root".to_string(),
+        }
+    }
+
+    fn run(inventory: &mut Inventory, items: &[TreeItem]) -> (usize, usize, usize, usize) {
+        // last_seen has millisecond precision
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        inventory.begin("root").unwrap();
+        for item in items {
+            inventory.record(item).unwrap();
+        }
+        let stats = inventory.finish().unwrap();
+        (stats.added, stats.changed, stats.unchanged, stats.removed)
+    }
+
+    #[test]
+    fn record_tracks_added_changed_and_removed_files() {
+        let mut inventory = Inventory::open(":memory:").unwrap();
+        let first = [item("a", "a.txt", 100), item("b", "dir/b.txt", 100)];
+        assert_eq!(run(&mut inventory, &first), (2, 0, 0, 0));
+
+        let second = [item("a", "a.txt", 200), item("c", "c.txt", 100)];
+        assert_eq!(run(&mut inventory, &second), (1, 1, 0, 1));
+
+        let removed: String = inventory
+            .conn
+            .query_row(
+                "SELECT path FROM files WHERE removed_time IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(removed, "dir/b.txt");
+        let runs: i64 = inventory
+            .conn
+            .query_row("SELECT COUNT(*) FROM runs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(runs, 2);
+    }
+
+    #[test]
+    fn record_brings_back_a_removed_file() {
+        let mut inventory = Inventory::open(":memory:").unwrap();
+        run(&mut inventory, &[item("a", "a.txt", 100)]);
+        run(&mut inventory, &[]);
+        assert_eq!(
+            run(&mut inventory, &[item("a", "a.txt", 100)]),
+            (1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn touch_moves_known_files_and_keeps_them() {
+        let mut inventory = Inventory::open(":memory:").unwrap();
+        assert_eq!(inventory.last_run("root").unwrap(), None);
+        run(
+            &mut inventory,
+            &[item("a", "a.txt", 100), item("b", "b.txt", 100)],
+        );
+        let first_run = inventory.last_run("root").unwrap().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        inventory.begin("root").unwrap();
+        assert!(inventory.touch(&item("a", "dir/a.txt", 300)).unwrap());
+        assert!(!inventory.touch(&item("c", "c.txt", 100)).unwrap());
+        let stats = inventory.finish().unwrap();
+        assert_eq!((stats.unchanged, stats.removed), (1, 1));
+        assert!(inventory.last_run("root").unwrap().unwrap() > first_run);
+
+        let (path, modified): (String, String) = inventory
+            .conn
+            .query_row(
+                "SELECT path, modified_time FROM files WHERE id = 'a'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        // only the place changes, the metadata is left for a full record
+        assert_eq!(path, "dir/a.txt");
+        assert_eq!(
+            modified,
+            timestamp(DateTime::from_timestamp(100, 0)).unwrap()
+        );
+    }
+}
@@ -1,11 +1,35 @@
 use anyhow::{Ok, Result};
-use cli::{Cli, Commands, Parser};
+use cli::{Cli, Commands, Parser, RevisionCommands, TrashCommands};
 use drive_client::get_drive_client;
 
-use crate::utils::{download_files, list_files, upload};
+use crate::{
+    download::{download_archive, download_files},
+    dupes::find_dupes,
+    folders::{copy_files, make_dir, move_files, provision},
+    inventory::export_inventory,
+    paths::{resolve_id, resolve_path, search, stat},
+    rename::rename_files,
+    revisions::{download_revision, list_revisions},
+    sharing::{audit_sharing, share_from_csv, unshare},
+    trash::{empty_trash, list_trash, remove_files, restore_files},
+    utils::{list_files, list_tree, show_about, upload},
+};
 
+mod archive;
 mod cli;
+mod download;
 mod drive_client;
+mod dupes;
+mod folders;
+mod inventory;
+mod naming;
+mod output;
+mod paths;
+mod rename;
+mod revisions;
+mod sharing;
+mod template;
+mod trash;
 mod types;
 mod utils;
 
@@ -14,7 +38,32 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     let hub = get_drive_client(&cli.auth_method).await?;
     match &cli.command {
-        Commands::List { folder_id } => list_files(hub, folder_id).await?,
+        Commands::List {
+            folder_id,
+            format,
+            fields,
+            recursive,
+            tree,
+            depth,
+            concurrency,
+            filters,
+        } => {
+            if *tree {
+                list_tree(hub, folder_id, *depth, *concurrency).await?
+            } else {
+                list_files(
+                    hub,
+                    folder_id,
+                    format,
+                    fields,
+                    *recursive,
+                    *depth,
+                    *concurrency,
+                    filters,
+                )
+                .await?
+            }
+        }
         Commands::Upload {
             directory,
             folder_id,
@@ -22,9 +71,164 @@ async fn main() -> Result<()> {
         } => upload(hub, directory, folder_id, concurrency).await?,
         Commands::Download {
             path,
+            source,
+            concurrency,
+            name_style,
+            archive,
+            as_of,
+            refresh,
+        } => match (archive, path) {
+            (Some(archive), _) => {
+                download_archive(hub, source, archive, *concurrency, name_style, *as_of).await?
+            }
+            (None, Some(path)) => {
+                download_files(
+                    hub,
+                    source,
+                    path,
+                    *concurrency,
+                    name_style,
+                    *as_of,
+                    *refresh,
+                )
+                .await?
+            }
+            (None, None) => unreachable!("clap requires --path or --archive"),
+        },
+        Commands::About => show_about(hub).await?,
+        Commands::Dupes {
+            folder_id,
+            by_name,
+            trash,
+            keep,
+            yes,
+            concurrency,
+        } => find_dupes(hub, folder_id, *by_name, *trash, keep, *yes, *concurrency).await?,
+        Commands::Inventory {
+            folder_id,
+            db,
+            full,
+            concurrency,
+        } => export_inventory(hub, folder_id, db, *concurrency, *full).await?,
+        Commands::Resolve {
+            path,
+            id,
+            folder_id,
+        } => match (path, id) {
+            (Some(path), _) => resolve_path(hub, path, folder_id).await?,
+            (None, Some(id)) => resolve_id(hub, id).await?,
+            (None, None) => unreachable!("clap requires --path or --id"),
+        },
+        Commands::Cp {
+            src,
+            dest,
+            recursive,
+            properties,
+            concurrency,
+        } => copy_files(hub, src, dest, *recursive, *properties, *concurrency).await?,
+        Commands::Mkdir {
+            path,
+            parents,
+            folder_id,
+        } => make_dir(hub, path, folder_id, *parents).await?,
+        Commands::Provision { spec, folder_id } => provision(hub, spec, folder_id).await?,
+        Commands::Mv {
+            source,
+            to,
+            dry_run,
+            concurrency,
+        } => move_files(hub, source, to, *dry_run, *concurrency).await?,
+        Commands::Rename {
+            source,
+            pattern,
+            to,
+            dry_run,
+            yes,
+            concurrency,
+        } => rename_files(hub, source, pattern, to, *dry_run, *yes, *concurrency).await?,
+        Commands::Rm {
+            source,
+            filters,
+            permanent,
+            yes,
+            concurrency,
+        } => remove_files(hub, source, filters, *permanent, *yes, *concurrency).await?,
+        Commands::Search {
+            terms,
+            format,
+            fields,
+            limit,
+        } => search(hub, terms, format, fields, *limit).await?,
+        Commands::Share {
+            csv,
+            no_notify,
+            concurrency,
+        } => share_from_csv(hub, csv, !*no_notify, *concurrency).await?,
+        Commands::AuditSharing {
+            folder_id,
+            format,
+            fields,
+            exposed_only,
+            internal_domains,
+            concurrency,
+        } => {
+            audit_sharing(
+                hub,
+                folder_id,
+                format,
+                fields,
+                *exposed_only,
+                internal_domains,
+                *concurrency,
+            )
+            .await?
+        }
+        Commands::Unshare {
             folder_id,
+            filter,
+            downgrade_to,
+            format,
+            dry_run,
+            yes,
             concurrency,
-        } => download_files(hub, folder_id, path, *concurrency).await?,
+        } => {
+            unshare(
+                hub,
+                folder_id,
+                filter,
+                downgrade_to.as_deref(),
+                format,
+                *dry_run,
+                *yes,
+                *concurrency,
+            )
+            .await?
+        }
+        Commands::Stat { target, json } => stat(hub, target, *json).await?,
+        Commands::Trash { command } => match command {
+            TrashCommands::List {
+                format,
+                fields,
+                limit,
+            } => list_trash(hub, format, fields, *limit).await?,
+            TrashCommands::Restore {
+                ids,
+                query,
+                to,
+                concurrency,
+            } => restore_files(hub, ids, query.as_deref(), to.as_deref(), *concurrency).await?,
+            TrashCommands::Empty { drive_id, yes } => {
+                empty_trash(hub, drive_id.as_deref(), *yes).await?
+            }
+        },
+        Commands::Revisions { command } => match command {
+            RevisionCommands::List { file_id } => list_revisions(hub, file_id).await?,
+            RevisionCommands::Get {
+                file_id,
+                revision_id,
+                output,
+            } => download_revision(hub, file_id, revision_id, output).await?,
+        },
     };
     Ok(())
 }
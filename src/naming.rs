@@ -0,0 +1,362 @@
+use crate::{
+    cli::NameStyle,
+    types::{DownloadTarget, NameCollisions},
+};
+use anyhow::Result;
+use google_drive3::api::File;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+/// Gives every file in one batch (e.g. one folder) a unique, filesystem-safe
+/// local name. Within each group of equal names the oldest file (by
+/// `createdTime`, then ID) keeps the name.
+pub fn assign_local_names(mut files: Vec<File>, name_style: &NameStyle) -> Vec<DownloadTarget> {
+    files.sort_by(|a, b| (a.created_time, &a.id).cmp(&(b.created_time, &b.id)));
+    let mut namer = LocalNamer::new(name_style);
+    namer.reserve(files.iter().filter_map(|f| f.name.as_deref()));
+    files.into_iter().map(|f| namer.assign(f)).collect()
+}
+
+/// Hands out unique, filesystem-safe local names as files arrive. Names are
+/// sanitized first; Drive also allows several files with the same name in one
+/// folder, so the first file seen keeps the name and later ones get a ` (n)`
+/// suffix before the extension. Feed files oldest first for stable names.
+pub struct LocalNamer<'a> {
+    name_style: &'a NameStyle,
+    /// names kept free for the files that carry them
+    reserved: HashSet<String>,
+    /// local name -> Drive ID of the file it was given to
+    owners: HashMap<String, String>,
+    /// Drive names that had to change to be safe locally, as `(name, local_name)`
+    pub renamed: Vec<(String, String)>,
+    pub collisions: NameCollisions,
+}
+
+impl<'a> LocalNamer<'a> {
+    pub fn new(name_style: &'a NameStyle) -> Self {
+        Self {
+            name_style,
+            reserved: HashSet::new(),
+            owners: HashMap::new(),
+            renamed: Vec::new(),
+            collisions: BTreeMap::new(),
+        }
+    }
+
+    /// Keeps `names` for their own files, so a suffixed duplicate never takes
+    /// e.g. a literal `report (2).pdf` that comes later.
+    pub fn reserve<'n>(&mut self, names: impl Iterator<Item = &'n str>) {
+        let name_style = self.name_style;
+        self.reserved
+            .extend(names.map(|name| sanitize_name(name, name_style)));
+    }
+
+    pub fn assign(&mut self, file: File) -> DownloadTarget {
+        let name = file.name.clone().unwrap_or_default();
+        let id = file.id.clone().unwrap_or_default();
+        let base = sanitize_name(&name, self.name_style);
+        if base != name {
+            self.renamed.push((name, base.clone()));
+        }
+
+        let local_name = match self.owners.get(&base) {
+            None => base,
+            Some(first_id) => {
+                let report = self
+                    .collisions
+                    .entry(base.clone())
+                    .or_insert_with(|| vec![(first_id.clone(), base.clone())]);
+                let mut n = report.len();
+                let local_name = loop {
+                    n += 1;
+                    let candidate = numbered_name(&base, n);
+                    if !self.reserved.contains(&candidate) && !self.owners.contains_key(&candidate)
+                    {
+                        break candidate;
+                    }
+                };
+                report.push((id.clone(), local_name.clone()));
+                local_name
+            }
+        };
+        self.owners.insert(local_name.clone(), id);
+
+        DownloadTarget {
+            file,
+            local_name,
+            revision_id: None,
+        }
+    }
+}
+
+/// `report.pdf` -> `report (n).pdf`
+fn numbered_name(name: &str, n: usize) -> String {
+    let path = Path::new(name);
+    match (
+        path.file_stem().and_then(|s| s.to_str()),
+        path.extension().and_then(|s| s.to_str()),
+    ) {
+        (Some(stem), Some(ext)) => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", name, n),
+    }
+}
+
+/// Longest name we produce, leaving room for a ` (n)` suffix under the 255-byte
+/// limit of ext4 and most other filesystems.
+const MAX_NAME_BYTES: usize = 240;
+
+/// Turns a Drive name into a single safe path component. Path separators,
+/// control characters, characters Windows rejects, trailing dots and spaces,
+/// and reserved device names are either percent-encoded (reversible with any
+/// URL decoder; `%` itself is encoded too) or replaced with `_`. Names longer
+/// than [`MAX_NAME_BYTES`] are cut and tagged with a hash of the full name.
+fn sanitize_name(name: &str, style: &NameStyle) -> String {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    let body_end = name.trim_end_matches(['.', ' ']).len();
+    let stem = name.split('.').next().unwrap_or_default();
+    let is_reserved = RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem));
+
+    let mut out = String::with_capacity(name.len());
+    for (i, c) in name.char_indices() {
+        let is_unsafe = c.is_control()
+            || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+            || i >= body_end
+            || (i == 0 && is_reserved);
+        match style {
+            NameStyle::Percent if is_unsafe || c == '%' => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("%{:02X}", b));
+                }
+            }
+            NameStyle::Replace if i == 0 && is_reserved => {
+                out.push('_');
+                out.push(c);
+            }
+            NameStyle::Replace if is_unsafe => out.push('_'),
+            _ => out.push(c),
+        }
+    }
+
+    if out.is_empty() {
+        return "_".to_string();
+    }
+    if out.len() > MAX_NAME_BYTES {
+        out = shorten_name(&out, name);
+    }
+    out
+}
+
+/// Cuts `sanitized` to fit [`MAX_NAME_BYTES`], keeping a short extension and
+/// appending `~hash` of the `original` name so distinct long names stay distinct.
+fn shorten_name(sanitized: &str, original: &str) -> String {
+    // FNV-1a, stable across builds unlike `DefaultHasher`
+    let hash = original.bytes().fold(0x811c9dc5u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x01000193)
+    });
+    let tag = format!("~{:08x}", hash);
+
+    let ext = match sanitized.rfind('.') {
+        Some(dot) if sanitized.len() - dot <= 16 => &sanitized[dot..],
+        _ => "",
+    };
+    let mut cut = MAX_NAME_BYTES - tag.len() - ext.len();
+    while !sanitized.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let mut stem = &sanitized[..cut];
+    // never split a `%XX` escape
+    if let Some(pct) = stem
+        .len()
+        .checked_sub(2)
+        .and_then(|from| stem.as_bytes()[from..].iter().position(|&b| b == b'%'))
+    {
+        stem = &stem[..stem.len() - 2 + pct];
+    }
+    format!("{}{}{}", stem, tag, ext)
+}
+
+/// Joins `name` onto `dir`, refusing anything but a single plain path component
+/// so a download can never land outside the target directory.
+pub fn safe_join(dir: &str, name: &str) -> Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(Path::new(dir).join(name)),
+        _ => Err(anyhow::anyhow!(
+            "Refusing to write '{}' outside '{}'",
+            name,
+            dir
+        )),
+    }
+}
+
+pub fn report_sanitized_names(renamed: &[(String, String)]) {
+    if renamed.is_empty() {
+        return;
+    }
+    println!("Renaming {} files to filesystem-safe names:", renamed.len());
+    for (name, local_name) in renamed {
+        println!("   - '{}' -> '{}'", name, local_name);
+    }
+}
+
+pub fn report_name_collisions(collisions: &NameCollisions) {
+    if collisions.is_empty() {
+        return;
+    }
+    println!(
+        "Found {} names shared by several Drive files, renaming locally:",
+        collisions.len()
+    );
+    for (name, files) in collisions {
+        println!("   - '{}':", name);
+        for (id, local_name) in files {
+            println!("       {} -> '{}'", id, local_name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn file(id: &str, name: &str, created: i64) -> File {
+        File {
+            id: Some(id.to_string()),
+            name: Some(name.to_string()),
+            created_time: DateTime::from_timestamp(created, 0),
+            ..Default::default()
+        }
+    }
+
+    fn names(targets: &[DownloadTarget]) -> Vec<(&str, &str)> {
+        targets
+            .iter()
+            .map(|t| (t.file.id.as_deref().unwrap(), t.local_name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn numbered_name_goes_before_the_extension() {
+        assert_eq!(numbered_name("report.pdf", 2), "report (2).pdf");
+        assert_eq!(numbered_name("archive.tar.gz", 3), "archive.tar (3).gz");
+        assert_eq!(numbered_name("README", 2), "README (2)");
+    }
+
+    #[test]
+    fn the_oldest_file_keeps_a_shared_name() {
+        let files = vec![
+            file("new", "a.pdf", 300),
+            file("old", "a.pdf", 100),
+            file("mid", "a.pdf", 200),
+        ];
+        let targets = assign_local_names(files, &NameStyle::Percent);
+        assert_eq!(
+            names(&targets),
+            [("old", "a.pdf"), ("mid", "a (2).pdf"), ("new", "a (3).pdf")]
+        );
+    }
+
+    #[test]
+    fn suffixes_skip_names_taken_by_other_files() {
+        let files = vec![
+            file("1", "a.pdf", 100),
+            file("2", "a.pdf", 200),
+            file("3", "a (2).pdf", 300),
+        ];
+        let targets = assign_local_names(files, &NameStyle::Percent);
+        assert_eq!(
+            names(&targets),
+            [("1", "a.pdf"), ("2", "a (3).pdf"), ("3", "a (2).pdf")]
+        );
+    }
+
+    #[test]
+    fn local_namer_reports_collisions() {
+        let style = NameStyle::Percent;
+        let mut namer = LocalNamer::new(&style);
+        namer.assign(file("1", "a.pdf", 100));
+        namer.assign(file("2", "b.pdf", 100));
+        namer.assign(file("3", "a.pdf", 200));
+        assert_eq!(
+            namer.collisions["a.pdf"],
+            [
+                ("1".to_string(), "a.pdf".to_string()),
+                ("3".to_string(), "a (2).pdf".to_string())
+            ]
+        );
+        assert_eq!(namer.collisions.len(), 1);
+        assert!(namer.renamed.is_empty());
+    }
+
+    #[test]
+    fn sanitize_name_encodes_or_replaces_unsafe_characters() {
+        let percent = |name| sanitize_name(name, &NameStyle::Percent);
+        let replace = |name| sanitize_name(name, &NameStyle::Replace);
+        assert_eq!(percent("a/b.pdf"), "a%2Fb.pdf");
+        assert_eq!(percent("100%.txt"), "100%25.txt");
+        assert_eq!(percent("what?.txt"), "what%3F.txt");
+        assert_eq!(percent("tab\there"), "tab%09here");
+        assert_eq!(percent(r"a\b"), "a%5Cb");
+        assert_eq!(replace("a/b:c*.pdf"), "a_b_c_.pdf");
+        assert_eq!(replace("100%.txt"), "100%.txt");
+        assert_eq!(percent("plain name.pdf"), "plain name.pdf");
+    }
+
+    #[test]
+    fn sanitize_name_handles_dots_reserved_and_empty_names() {
+        assert_eq!(sanitize_name("notes. ", &NameStyle::Percent), "notes%2E%20");
+        assert_eq!(sanitize_name("notes..", &NameStyle::Replace), "notes__");
+        assert_eq!(sanitize_name("..", &NameStyle::Replace), "__");
+        assert_eq!(sanitize_name("con.txt", &NameStyle::Percent), "%63on.txt");
+        assert_eq!(sanitize_name("NUL", &NameStyle::Replace), "_NUL");
+        assert_eq!(
+            sanitize_name("console.txt", &NameStyle::Replace),
+            "console.txt"
+        );
+        assert_eq!(sanitize_name("", &NameStyle::Percent), "_");
+    }
+
+    #[test]
+    fn long_names_are_shortened_with_a_hash() {
+        let long = format!("{}.pdf", "x".repeat(300));
+        let other = format!("{}y.pdf", "x".repeat(300));
+        let short = sanitize_name(&long, &NameStyle::Percent);
+        assert!(short.len() <= MAX_NAME_BYTES);
+        assert!(short.ends_with(".pdf"));
+        assert_ne!(short, sanitize_name(&other, &NameStyle::Percent));
+        assert_eq!(short, sanitize_name(&long, &NameStyle::Percent));
+    }
+
+    #[test]
+    fn shorten_name_never_splits_escapes_or_characters() {
+        let escaped = "%2F".repeat(100);
+        let short = shorten_name(&escaped, "original");
+        let stem = short.split('~').next().unwrap();
+        assert_eq!(stem.len() % 3, 0);
+        assert!(stem.len() <= MAX_NAME_BYTES);
+
+        let wide = "é".repeat(200);
+        let short = shorten_name(&wide, &wide);
+        assert!(short.len() <= MAX_NAME_BYTES);
+        assert!(short.starts_with('é'));
+    }
+
+    #[test]
+    fn safe_join_only_takes_a_single_component() {
+        assert_eq!(
+            safe_join("out", "a.pdf").unwrap(),
+            Path::new("out").join("a.pdf")
+        );
+        for name in ["../a.pdf", "a/b.pdf", "/etc/passwd", "..", "."] {
+            assert!(safe_join("out", name).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::Result;
+use google_drive3::api::File;
+use serde_json::{Map, Value};
+use std::io::{StdoutLock, Write};
+
+use crate::cli::OutputFormat;
+
+/// Prints files to stdout one at a time as they arrive, showing only
+/// `fields` (Drive API names such as `id` or `md5Checksum`).
+/// `OutputFormat::Text` keeps the classic `- name (mime)` lines and ignores
+/// `fields`. Only tables are held back until [`FilePrinter::finish`], as their
+/// column widths depend on every row.
+pub struct FilePrinter<'a> {
+    format: &'a OutputFormat,
+    fields: &'a [String],
+    out: StdoutLock<'static>,
+    csv: Option<csv::Writer<StdoutLock<'static>>>,
+    table: Vec<Vec<String>>,
+    count: usize,
+}
+
+impl<'a> FilePrinter<'a> {
+    pub fn new(format: &'a OutputFormat, fields: &'a [String]) -> Result<Self> {
+        let mut csv = None;
+        if let OutputFormat::Csv = format {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(fields)?;
+            csv = Some(writer);
+        }
+        Ok(Self {
+            format,
+            fields,
+            out: std::io::stdout().lock(),
+            csv,
+            table: Vec::new(),
+            count: 0,
+        })
+    }
+
+    pub fn print(&mut self, file: &File) -> Result<()> {
+        self.print_with(file, &Map::new())
+    }
+
+    /// Prints `file` with `extra` values that are not Drive file fields,
+    /// such as a resolved `path`. They fill the fields of the same name, and
+    /// the text format appends them to each line.
+    pub fn print_with(&mut self, file: &File, extra: &Map<String, Value>) -> Result<()> {
+        let out = &mut self.out;
+        match self.format {
+            OutputFormat::Text => {
+                write!(
+                    out,
+                    "- {} ({})",
+                    file.name.as_deref().unwrap_or_default(),
+                    file.mime_type.as_deref().unwrap_or_default()
+                )?;
+                for value in extra.values() {
+                    write!(out, "  {}", render(value))?;
+                }
+                writeln!(out)?;
+            }
+            OutputFormat::Json => {
+                let row = select_fields(file, self.fields, extra)?;
+                let json = serde_json::to_string_pretty(&row)?.replace('\n', "\n  ");
+                write!(
+                    out,
+                    "{}\n  {}",
+                    if self.count == 0 { "[" } else { "," },
+                    json
+                )?;
+            }
+            OutputFormat::Jsonl => {
+                serde_json::to_writer(&mut *out, &select_fields(file, self.fields, extra)?)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Csv => {
+                let row = select_fields(file, self.fields, extra)?;
+                if let Some(writer) = &mut self.csv {
+                    writer.write_record(self.fields.iter().map(|field| render(&row[field])))?;
+                }
+            }
+            OutputFormat::Table => {
+                let row = select_fields(file, self.fields, extra)?;
+                self.table.push(
+                    self.fields
+                        .iter()
+                        .map(|field| render(&row[field]))
+                        .collect(),
+                );
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Prints a row that is not a Drive file, such as a report line, with
+    /// `record` holding every field. The text format shows its values in
+    /// order on one line.
+    pub fn print_record(&mut self, record: &Map<String, Value>) -> Result<()> {
+        if let OutputFormat::Text = self.format {
+            let values: Vec<String> = record.values().map(render).collect();
+            writeln!(self.out, "{}", values.join("  "))?;
+            self.count += 1;
+            return Ok(());
+        }
+        self.print_with(&File::default(), record)
+    }
+
+    /// Closes the output and returns how many files were printed.
+    pub fn finish(mut self) -> Result<usize> {
+        match self.format {
+            OutputFormat::Json if self.count == 0 => writeln!(self.out, "[]")?,
+            OutputFormat::Json => writeln!(self.out, "\n]")?,
+            OutputFormat::Csv => {
+                if let Some(writer) = &mut self.csv {
+                    writer.flush()?;
+                }
+            }
+            OutputFormat::Table => print_table(&mut self.out, self.fields, &self.table)?,
+            _ => {}
+        }
+        self.out.flush()?;
+        Ok(self.count)
+    }
+}
+
+/// Prints `rows` to stdout as aligned columns under `headers`, like the
+/// table format of file listings.
+pub fn print_rows(headers: &[String], rows: &[Vec<String>]) -> Result<()> {
+    print_table(&mut std::io::stdout().lock(), headers, rows)
+}
+
+fn print_table(out: &mut impl Write, fields: &[String], cells: &[Vec<String>]) -> Result<()> {
+    let widths: Vec<usize> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([field.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |values: &[String]| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{:<w$}", v, w = w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    writeln!(out, "{}", line(fields))?;
+    writeln!(
+        out,
+        "{}",
+        widths
+            .iter()
+            .map(|w| "-".repeat(*w))
+            .collect::<Vec<_>>()
+            .join("  ")
+    )?;
+    for row in cells {
+        writeln!(out, "{}", line(row))?;
+    }
+    Ok(())
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.50 GB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+/// Picks `fields` out of `extra` or the JSON form of `file`; missing fields
+/// become `null`.
+fn select_fields(
+    file: &File,
+    fields: &[String],
+    extra: &Map<String, Value>,
+) -> Result<Map<String, Value>> {
+    let Value::Object(mut all) = serde_json::to_value(file)? else {
+        return Ok(Map::new());
+    };
+    Ok(fields
+        .iter()
+        .map(|field| {
+            let value = match extra.get(field) {
+                Some(value) => value.clone(),
+                None => all.remove(field).map(strip_nulls).unwrap_or(Value::Null),
+            };
+            (field.clone(), value)
+        })
+        .collect())
+}
+
+/// Drops `null` members from nested objects, which Drive leaves for every
+/// sub-field that was not requested.
+pub fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, strip_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(strip_nulls).collect()),
+        other => other,
+    }
+}
+
+/// Flattens a JSON value into one CSV or table cell. Lists are joined with
+/// `;`, and users (e.g. `owners`) are shown by email address.
+fn render(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(render).collect::<Vec<_>>().join(";"),
+        Value::Object(map) => match map.get("emailAddress").or(map.get("displayName")) {
+            Some(v) => render(v),
+            None => value.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
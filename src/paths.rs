@@ -0,0 +1,452 @@
+use crate::{
+    cli::OutputFormat,
+    output::{FilePrinter, format_size, strip_nulls},
+    revisions::get_revisions,
+    types::TDriveHub,
+    utils::{FILE_FIELDS, FOLDER_MIME_TYPE, escape_query, is_not_found, query_files},
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use google_drive3::api::File;
+use std::{collections::HashMap, sync::Arc};
+
+/// Searches file contents and names across My Drive, shared drives and items
+/// shared with the account; every word of `terms` has to match. Results are
+/// shown with their folder path and owner, and `path` can also be used as a
+/// field in the other formats.
+pub async fn search(
+    hub: TDriveHub,
+    terms: &str,
+    format: &OutputFormat,
+    fields: &[String],
+    limit: Option<usize>,
+) -> Result<()> {
+    let words: Vec<String> = terms.split_whitespace().map(escape_query).collect();
+    if words.is_empty() {
+        return Err(anyhow::anyhow!("Nothing to search for"));
+    }
+    let query = words
+        .iter()
+        .map(|w| format!("(fullText contains '{}' or name contains '{}')", w, w))
+        .chain(["trashed=false".to_string()])
+        .collect::<Vec<_>>()
+        .join(" and ");
+
+    let text = matches!(format, OutputFormat::Text);
+    let want_path = text || fields.iter().any(|f| f == "path");
+    let api_fields = search_fields(text, fields);
+
+    let mut results = query_files(Arc::clone(&hub), &query, &api_fields, None, true)
+        .take(limit.unwrap_or(usize::MAX));
+    let mut paths = PathResolver::new(&hub);
+    let mut printer = FilePrinter::new(format, fields)?;
+    while let Some(file) = results.try_next().await? {
+        let mut extra = serde_json::Map::new();
+        if want_path {
+            extra.insert("path".to_string(), paths.path_of(&file).await.into());
+        }
+        if text {
+            let owner = file
+                .owners
+                .iter()
+                .flatten()
+                .find_map(|o| o.email_address.clone().or_else(|| o.display_name.clone()));
+            extra.insert("owner".to_string(), owner.unwrap_or_default().into());
+        }
+        printer.print_with(&file, &extra)?;
+    }
+    let count = printer.finish()?;
+
+    if text {
+        match count {
+            0 => println!("No files match '{}'", terms),
+            n => println!("{} results", n),
+        }
+    }
+    Ok(())
+}
+
+/// The API fields `search` asks for: everything the text listing shows, or
+/// the requested fields, plus whatever the folder path is built from.
+fn search_fields(text: bool, fields: &[String]) -> String {
+    let want_path = text || fields.iter().any(|f| f == "path");
+    let mut api_fields: Vec<&str> = match text {
+        true => FILE_FIELDS.split(", ").chain(["owners"]).collect(),
+        false => fields
+            .iter()
+            .map(String::as_str)
+            .filter(|f| *f != "path")
+            .collect(),
+    };
+    for field in ["name", "parents"] {
+        if want_path && !api_fields.contains(&field) {
+            api_fields.push(field);
+        }
+    }
+    api_fields.join(", ")
+}
+
+/// Builds full Drive paths by following `parents` upwards, remembering every
+/// folder on the way so results in the same folders cost no extra requests.
+struct PathResolver {
+    hub: TDriveHub,
+    /// folder ID -> full path
+    paths: HashMap<String, String>,
+}
+
+impl PathResolver {
+    fn new(hub: &TDriveHub) -> Self {
+        Self {
+            hub: Arc::clone(hub),
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Full path of `file` through its first parent.
+    async fn path_of(&mut self, file: &File) -> String {
+        let name = file.name.as_deref().unwrap_or_default();
+        match file.parents.iter().flatten().next() {
+            Some(parent) => format!("{}/{}", self.folder_path(parent).await, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Full path of a folder, up to My Drive, a shared drive or the highest
+    /// folder this account can see (shown as `…`).
+    async fn folder_path(&mut self, folder_id: &str) -> String {
+        // (ID, name) from `folder_id` upwards, until a known folder or the top
+        let mut chain = Vec::new();
+        let mut id = folder_id.to_string();
+        let mut path = loop {
+            if let Some(path) = self.paths.get(&id) {
+                break path.clone();
+            }
+            let result = self
+                .hub
+                .files()
+                .get(&id)
+                .supports_all_drives(true)
+                .param("fields", "name, parents")
+                .add_scope(google_drive3::api::Scope::Full)
+                .doit()
+                .await;
+            // the length check guards against cycles, which Drive should never produce
+            let (Ok((_, folder)), true) = (result, chain.len() < 100) else {
+                self.paths.insert(id, "…".to_string());
+                break "…".to_string();
+            };
+            chain.push((id, folder.name.clone().unwrap_or_default()));
+            match folder.parents.iter().flatten().next() {
+                Some(parent) => id = parent.clone(),
+                None => break String::new(),
+            }
+        };
+        for (id, name) in chain.into_iter().rev() {
+            path = match path.is_empty() {
+                true => name,
+                false => format!("{}/{}", path, name),
+            };
+            self.paths.insert(id, path.clone());
+        }
+        path
+    }
+}
+
+/// Prints the ID of the file or folder at `path` (e.g. `Finance/2026`).
+pub async fn resolve_path(hub: TDriveHub, path: &str, root: &str) -> Result<()> {
+    println!("{}", find_path_id(&hub, path, root).await?);
+    Ok(())
+}
+
+/// Looks up the ID of the item at `path`, one name at a time starting from
+/// `root`. From My Drive the first name may also be a shared drive. Drive
+/// allows several items with the same name in one folder, so every match is
+/// followed; when more than one is left at the end they are all reported on
+/// stderr and an error is returned.
+async fn find_path_id(hub: &TDriveHub, path: &str, root: &str) -> Result<String> {
+    let names: Vec<&str> = path.split('/').filter(|n| !n.is_empty()).collect();
+    // each candidate is the chain of IDs leading to it
+    let mut candidates = vec![vec![root.to_string()]];
+
+    for (i, name) in names.iter().enumerate() {
+        let last = i + 1 == names.len();
+        let mut next = Vec::new();
+
+        if i == 0 && root == "root" {
+            let (_, drives) = hub
+                .drives()
+                .list()
+                .q(&format!("name = '{}'", escape_query(name)))
+                .page_size(100)
+                .add_scope(google_drive3::api::Scope::Full)
+                .doit()
+                .await?;
+            for drive in drives.drives.unwrap_or_default() {
+                next.push(vec![drive.id.unwrap_or_default()]);
+            }
+        }
+
+        for chain in &candidates {
+            let parent_id = chain.last().unwrap();
+            let mut query = format!(
+                "'{}' in parents and name = '{}' and trashed=false",
+                parent_id,
+                escape_query(name)
+            );
+            if !last {
+                query = format!("{} and mimeType = '{}'", query, FOLDER_MIME_TYPE);
+            }
+            let mut children = query_files(Arc::clone(hub), &query, "id", None, false);
+            while let Some(child) = children.try_next().await? {
+                let mut chain = chain.clone();
+                chain.push(child.id.unwrap_or_default());
+                next.push(chain);
+            }
+        }
+
+        if next.is_empty() {
+            let parent = match i {
+                0 => root.to_string(),
+                _ => names[..i].join("/"),
+            };
+            return Err(anyhow::anyhow!("'{}' not found in '{}'", name, parent));
+        }
+        candidates = next;
+    }
+
+    if let [chain] = candidates.as_slice() {
+        return Ok(chain.last().unwrap().clone());
+    }
+    eprintln!("'{}' matches {} items:", path, candidates.len());
+    for chain in &candidates {
+        eprintln!("  {}  (via {})", chain.last().unwrap(), chain.join(" / "));
+    }
+    Err(anyhow::anyhow!(
+        "'{}' is ambiguous, use one of the IDs above",
+        path
+    ))
+}
+
+/// Prints every full path of `file_id`, found by following `parents` up to
+/// My Drive, a shared drive or the highest folder this account can see
+/// (shown as `…`). Items with several parents have one path per parent.
+pub async fn resolve_id(hub: TDriveHub, file_id: &str) -> Result<()> {
+    let (_, file) = hub
+        .files()
+        .get(file_id)
+        .supports_all_drives(true)
+        .param("fields", "name, parents")
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    let name = file.name.unwrap_or_default();
+    let parents = file.parents.unwrap_or_default();
+    if parents.len() > 1 {
+        eprintln!("'{}' has {} paths:", file_id, parents.len());
+    }
+    if parents.is_empty() {
+        println!("{}", name);
+    }
+    // folders shared by several paths are only looked up once
+    let mut resolver = PathResolver::new(&hub);
+    for parent in parents {
+        println!("{}/{}", resolver.folder_path(&parent).await, name);
+    }
+    Ok(())
+}
+
+/// Prints everything Drive knows about one item, given by ID or by path
+/// (see [`find_path_id`]), as aligned text or as JSON with an added
+/// `revisionCount`.
+pub async fn stat(hub: TDriveHub, target: &str, json: bool) -> Result<()> {
+    let is_path = target.contains('/');
+    let id = match is_path {
+        true => find_path_id(&hub, target, "root").await?,
+        false => target.to_string(),
+    };
+    let file = match get_full_file(&hub, &id).await {
+        Ok(file) => file,
+        // a single name that is not an ID we can see may still be a path
+        Err(e)
+            if !is_path
+                && e.downcast_ref::<google_drive3::Error>()
+                    .is_some_and(is_not_found) =>
+        {
+            let id = find_path_id(&hub, target, "root").await?;
+            get_full_file(&hub, &id).await?
+        }
+        Err(e) => return Err(e),
+    };
+    let revision_count = match file.mime_type.as_deref() {
+        Some(FOLDER_MIME_TYPE) => None,
+        _ => get_revisions(&hub, file.id.as_deref().unwrap_or_default())
+            .await
+            .ok()
+            .map(|revisions| revisions.len()),
+    };
+
+    if json {
+        let mut value = strip_nulls(serde_json::to_value(&file)?);
+        if let serde_json::Value::Object(map) = &mut value {
+            map.insert("revisionCount".to_string(), revision_count.into());
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+
+    let time = |t: Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339()).unwrap_or_default();
+    let user = |u: &google_drive3::api::User| {
+        u.email_address
+            .clone()
+            .or_else(|| u.display_name.clone())
+            .unwrap_or_default()
+    };
+    let yes_no = |b: Option<bool>| match b {
+        Some(true) => "yes".to_string(),
+        Some(false) => "no".to_string(),
+        None => String::new(),
+    };
+
+    let mut rows = vec![
+        ("Name", file.name.clone().unwrap_or_default()),
+        ("ID", file.id.clone().unwrap_or_default()),
+        ("MIME type", file.mime_type.clone().unwrap_or_default()),
+        (
+            "Size",
+            file.size
+                .map(|size| format!("{} ({} bytes)", format_size(size.max(0) as u64), size))
+                .unwrap_or_default(),
+        ),
+        ("MD5", file.md5_checksum.clone().unwrap_or_default()),
+        ("SHA-1", file.sha1_checksum.clone().unwrap_or_default()),
+        ("SHA-256", file.sha256_checksum.clone().unwrap_or_default()),
+        ("Created", time(file.created_time)),
+        ("Modified", time(file.modified_time)),
+        (
+            "Modified by",
+            file.last_modifying_user
+                .as_ref()
+                .map(user)
+                .unwrap_or_default(),
+        ),
+        (
+            "Owners",
+            file.owners
+                .iter()
+                .flatten()
+                .map(user)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        (
+            "Parents",
+            file.parents.clone().unwrap_or_default().join(", "),
+        ),
+        ("Shared drive", file.drive_id.clone().unwrap_or_default()),
+        ("Shared", yes_no(file.shared)),
+        ("Starred", yes_no(file.starred)),
+        ("Trashed", yes_no(file.trashed)),
+        ("Link", file.web_view_link.clone().unwrap_or_default()),
+    ];
+
+    let permissions: Vec<String> = file
+        .permissions
+        .iter()
+        .flatten()
+        .map(|p| {
+            let who = match p.type_.as_deref() {
+                Some("anyone") => "anyone with the link".to_string(),
+                Some("domain") => p.domain.clone().unwrap_or_default(),
+                _ => p
+                    .email_address
+                    .clone()
+                    .or_else(|| p.display_name.clone())
+                    .unwrap_or_default(),
+            };
+            format!("{} ({})", who, p.role.as_deref().unwrap_or_default())
+        })
+        .collect();
+    rows.push(("Permissions", permissions.join(", ")));
+
+    // capability names as Drive spells them, e.g. canEdit
+    let capabilities = match serde_json::to_value(&file.capabilities)? {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter(|(_, v)| v.as_bool() == Some(true))
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => String::new(),
+    };
+    rows.push(("Capabilities", capabilities));
+
+    let restrictions: Vec<String> = file
+        .content_restrictions
+        .iter()
+        .flatten()
+        .filter(|r| r.read_only == Some(true))
+        .map(|r| {
+            format!(
+                "read-only by {}{}",
+                r.restricting_user.as_ref().map(user).unwrap_or_default(),
+                r.reason
+                    .as_deref()
+                    .map(|reason| format!(": {}", reason))
+                    .unwrap_or_default()
+            )
+        })
+        .collect();
+    rows.push(("Restrictions", restrictions.join("; ")));
+
+    if let Some(shortcut) = &file.shortcut_details {
+        rows.push((
+            "Shortcut to",
+            format!(
+                "{} ({})",
+                shortcut.target_id.as_deref().unwrap_or_default(),
+                shortcut.target_mime_type.as_deref().unwrap_or_default()
+            ),
+        ));
+    }
+    rows.push((
+        "Revisions",
+        revision_count.map(|n| n.to_string()).unwrap_or_default(),
+    ));
+
+    for (label, value) in rows.into_iter().filter(|(_, v)| !v.is_empty()) {
+        println!("{:<14}{}", format!("{}:", label), value);
+    }
+    Ok(())
+}
+
+/// Fetches every field Drive has for `file_id`.
+async fn get_full_file(hub: &TDriveHub, file_id: &str) -> Result<File> {
+    let (_, file) = hub
+        .files()
+        .get(file_id)
+        .supports_all_drives(true)
+        .param("fields", "*")
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_fields_include_what_the_path_needs() {
+        let text = search_fields(true, &[]);
+        let text: Vec<&str> = text.split(", ").collect();
+        assert!(text.contains(&"parents"));
+        assert!(text.contains(&"owners"));
+        assert_eq!(text.iter().filter(|f| **f == "name").count(), 1);
+
+        let fields = ["id".to_string(), "path".to_string()];
+        assert_eq!(search_fields(false, &fields), "id, name, parents");
+        assert_eq!(search_fields(false, &fields[..1]), "id");
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    cli::SourceArgs,
+    output::print_rows,
+    template::NameTemplate,
+    types::TDriveHub,
+    utils::{FILE_FIELDS, confirm, get_files, stream_source_files, with_retry},
+};
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt, future::ready};
+use google_drive3::api::File;
+use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+/// Renames the selected files whose name matches `pattern`, replacing the
+/// first match with `to` (see [`NameTemplate`] for placeholders). Files are
+/// numbered for `{seq}` in name order. A preview table is printed first;
+/// nothing is renamed if any new name is empty or would clash with another
+/// name in the same folder, and the rest needs confirmation unless `yes`.
+pub async fn rename_files(
+    hub: TDriveHub,
+    source: &SourceArgs,
+    pattern: &Regex,
+    to: &str,
+    dry_run: bool,
+    yes: bool,
+    max_concurrency: usize,
+) -> Result<()> {
+    let start_time = Instant::now();
+    let template = NameTemplate::parse(to, pattern)?;
+    // parents are needed to find clashes within each folder
+    let fields = format!("{}, parents", FILE_FIELDS);
+    let files: Vec<File> =
+        stream_source_files(&hub, source, &fields, max_concurrency, Some("name"))?
+            .try_collect()
+            .await?;
+
+    let mut renames = Vec::new();
+    let mut seq = 0;
+    for file in &files {
+        let name = file.name.as_deref().unwrap_or_default();
+        let Some(caps) = pattern.captures(name) else {
+            continue;
+        };
+        seq += 1;
+        let whole = caps.get(0).unwrap();
+        let new_name = format!(
+            "{}{}{}",
+            &name[..whole.start()],
+            template.render(&caps, file, seq),
+            &name[whole.end()..]
+        );
+        if new_name != name {
+            renames.push((file, new_name));
+        }
+    }
+    if renames.is_empty() {
+        println!("No names to change ({} files checked)", files.len());
+        return Ok(());
+    }
+
+    // names that stay as they are, per folder
+    let renamed_ids: HashSet<&str> = renames
+        .iter()
+        .filter_map(|(f, _)| f.id.as_deref())
+        .collect();
+    let parents: HashSet<&String> = renames
+        .iter()
+        .flat_map(|(f, _)| f.parents.iter().flatten())
+        .collect();
+    let mut staying: HashSet<(String, String)> = HashSet::new();
+    for parent in parents {
+        let siblings: Vec<File> = match &source.folder_id {
+            Some(folder_id) if folder_id == parent => files.clone(),
+            _ => {
+                get_files(Arc::clone(&hub), parent, "id, name")
+                    .try_collect()
+                    .await?
+            }
+        };
+        for sibling in siblings {
+            if !renamed_ids.contains(sibling.id.as_deref().unwrap_or_default()) {
+                staying.insert((parent.clone(), sibling.name.unwrap_or_default()));
+            }
+        }
+    }
+    let mut new_names: HashMap<(String, String), usize> = HashMap::new();
+    for (file, new_name) in &renames {
+        for parent in file.parents.iter().flatten() {
+            *new_names
+                .entry((parent.clone(), new_name.clone()))
+                .or_default() += 1;
+        }
+    }
+
+    let mut problems = 0;
+    let rows: Vec<Vec<String>> = renames
+        .iter()
+        .map(|(file, new_name)| {
+            let clashes = file.parents.iter().flatten().any(|parent| {
+                let key = (parent.clone(), new_name.clone());
+                new_names[&key] > 1 || staying.contains(&key)
+            });
+            let note = match (new_name.trim().is_empty(), clashes) {
+                (true, _) => "empty name",
+                (false, true) => "name already taken",
+                (false, false) => "",
+            };
+            if !note.is_empty() {
+                problems += 1;
+            }
+            vec![
+                file.id.clone().unwrap_or_default(),
+                file.name.clone().unwrap_or_default(),
+                new_name.clone(),
+                note.to_string(),
+            ]
+        })
+        .collect();
+    let headers = ["id", "name", "new name", "problem"].map(String::from);
+    print_rows(&headers, &rows)?;
+
+    if problems > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} new names are empty or clash with other names in the same folder, nothing was renamed",
+            problems,
+            renames.len()
+        ));
+    }
+    if dry_run {
+        println!("\nDry run: {} files would be renamed", renames.len());
+        return Ok(());
+    }
+    let question = format!("Rename {} files", renames.len());
+    if !yes && !confirm(&question, source.ids_from.as_deref() == Some("-"))? {
+        println!("Nothing renamed");
+        return Ok(());
+    }
+
+    let overall_progress = ProgressBar::new(renames.len() as u64);
+    overall_progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Renaming [{bar:40.cyan/blue}] {pos}/{len} files ({percent}%) | {elapsed_precise} | ETA: {eta_precise}")
+            .unwrap()
+            .progress_chars("█▓▒░ ")
+    );
+    let retry_count = AtomicUsize::new(0);
+    let failed = futures::stream::iter(&renames)
+        .map(|(file, new_name)| {
+            let hub = &hub;
+            let retries = &retry_count;
+            let overall_prog = &overall_progress;
+            async move {
+                let id = file.id.as_deref().unwrap_or_default();
+                let name = file.name.as_deref().unwrap_or_default();
+                let result = with_retry("Rename", name, 3, retries, || {
+                    rename_file(hub, id, new_name)
+                })
+                .await;
+                overall_prog.inc(1);
+                overall_prog.set_message(new_name.clone());
+                if let Err(e) = &result {
+                    overall_prog.suspend(|| eprintln!("✗ Failed '{}' ({}): {}", name, id, e));
+                }
+                result.is_err()
+            }
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .filter(|failed| ready(*failed))
+        .count()
+        .await;
+    overall_progress.finish_with_message("Rename complete!");
+
+    println!("\nRename Summary:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("Renamed: {}", renames.len() - failed);
+    println!("Failed: {}", failed);
+    println!("Total retries: {}", retry_count.load(Ordering::Relaxed));
+    println!("Total time: {:?}", start_time.elapsed());
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} files could not be renamed", failed));
+    }
+    Ok(())
+}
+
+async fn rename_file(hub: &TDriveHub, file_id: &str, new_name: &str) -> Result<()> {
+    let update = File {
+        name: Some(new_name.to_string()),
+        ..Default::default()
+    };
+    hub.files()
+        .update(update, file_id)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit_without_upload()
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use crate::{
+    download::fetch_file_bytes,
+    types::{DownloadTarget, TDriveHub},
+    utils::{NATIVE_MIME_PREFIX, with_retry},
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use google_drive3::api::{File, Revision};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::atomic::AtomicUsize;
+
+/// Points `target` at its latest revision modified at or before `as_of`, so
+/// the download reflects the file as it was then. Files that did not exist
+/// yet, or whose revision history no longer reaches back that far, are
+/// skipped with a note on stderr. Native Google files (Docs, Sheets, Slides)
+/// are refused: their revisions have no content to download.
+pub async fn pin_revision(
+    hub: &TDriveHub,
+    mut target: DownloadTarget,
+    as_of: DateTime<Utc>,
+    retry_count: &AtomicUsize,
+) -> Result<Option<DownloadTarget>> {
+    if let Some(kind) = target
+        .file
+        .mime_type
+        .as_deref()
+        .and_then(|mime| mime.strip_prefix(NATIVE_MIME_PREFIX))
+    {
+        return Err(anyhow::anyhow!(
+            "'{}' is a Google {} file, --as-of cannot download its revisions",
+            target.local_name,
+            kind
+        ));
+    }
+    let file_id = target.file.id.clone().unwrap_or_default();
+    let revisions = with_retry("List revisions", &target.local_name, 3, retry_count, || {
+        get_revisions(hub, &file_id)
+    })
+    .await
+    .map_err(|e| {
+        e.context(format!(
+            "Failed to list revisions of '{}'",
+            target.local_name
+        ))
+    })?;
+    let Some(revision) = revision_as_of(revisions, as_of) else {
+        eprintln!(
+            "Skipping '{}': no revision at or before {}",
+            target.local_name,
+            as_of.to_rfc3339()
+        );
+        return Ok(None);
+    };
+    target.revision_id = revision.id;
+    target.file.size = revision.size;
+    target.file.modified_time = revision.modified_time;
+    Ok(Some(target))
+}
+
+/// The latest of `revisions` modified at or before `as_of`.
+fn revision_as_of(revisions: Vec<Revision>, as_of: DateTime<Utc>) -> Option<Revision> {
+    revisions
+        .into_iter()
+        .filter(|r| r.modified_time.is_some_and(|t| t <= as_of))
+        .max_by_key(|r| r.modified_time)
+}
+
+/// Fetches the stored revisions of a file, oldest first.
+pub async fn get_revisions(hub: &TDriveHub, file_id: &str) -> Result<Vec<Revision>> {
+    let mut all_revisions = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request = hub
+            .revisions()
+            .list(file_id)
+            .page_size(1000)
+            .param(
+                "fields",
+                "nextPageToken, revisions(id, modifiedTime, size, md5Checksum, keepForever, lastModifyingUser(displayName, emailAddress))",
+            )
+            .add_scope(google_drive3::api::Scope::Full);
+        if let Some(token) = page_token {
+            request = request.page_token(&token);
+        }
+        let (_, revision_list) = request.doit().await?;
+        if let Some(revisions) = revision_list.revisions {
+            all_revisions.extend(revisions);
+        }
+        page_token = revision_list.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+    }
+    Ok(all_revisions)
+}
+
+pub async fn list_revisions(hub: TDriveHub, file_id: &str) -> Result<()> {
+    let revisions = get_revisions(&hub, file_id).await?;
+    if revisions.is_empty() {
+        println!("No revisions found for file {}", file_id);
+        return Ok(());
+    }
+    println!("Revisions of file {}:", file_id);
+    for r in revisions {
+        println!(
+            "- {} | {} | {} bytes | md5 {} | {}{}",
+            r.id.unwrap_or_default(),
+            r.modified_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            r.size.unwrap_or(0),
+            r.md5_checksum.unwrap_or_else(|| "-".to_string()),
+            r.last_modifying_user
+                .and_then(|u| u.email_address.or(u.display_name))
+                .unwrap_or_default(),
+            if r.keep_forever == Some(true) {
+                " (kept forever)"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+pub async fn download_revision(
+    hub: TDriveHub,
+    file_id: &str,
+    revision_id: &str,
+    output: &str,
+) -> Result<()> {
+    let target = DownloadTarget {
+        file: File {
+            id: Some(file_id.to_string()),
+            ..Default::default()
+        },
+        local_name: output.to_string(),
+        revision_id: Some(revision_id.to_string()),
+    };
+    let progress = ProgressBar::new_spinner();
+    progress.set_style(
+        ProgressStyle::default_spinner()
+            .template("  {spinner} {bytes} downloaded")
+            .unwrap(),
+    );
+    let retries = AtomicUsize::new(0);
+    let data = with_retry("Download", output, 3, &retries, || {
+        fetch_file_bytes(&hub, &target, &progress)
+    })
+    .await?;
+    progress.finish_and_clear();
+    std::fs::write(output, &data)?;
+    println!(
+        "✓ Saved revision {} of {} to '{}' ({} bytes)",
+        revision_id,
+        file_id,
+        output,
+        data.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision(id: &str, modified: Option<&str>) -> Revision {
+        Revision {
+            id: Some(id.to_string()),
+            modified_time: modified.map(|t| t.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn revision_as_of_picks_the_latest_not_after() {
+        let revisions = vec![
+            revision("1", Some("2026-10-01T09:00:00Z")),
+            revision("3", Some("2026-10-13T18:00:00Z")),
+            revision("2", Some("2026-10-10T12:00:00Z")),
+            revision("4", Some("2026-10-14T08:00:00Z")),
+        ];
+        let picked = revision_as_of(revisions.clone(), at("2026-10-13T18:00:00Z")).unwrap();
+        assert_eq!(picked.id.as_deref(), Some("3"));
+        let picked = revision_as_of(revisions, at("2026-10-12T00:00:00Z")).unwrap();
+        assert_eq!(picked.id.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn revision_as_of_none_before() {
+        let revisions = vec![revision("1", Some("2026-10-01T09:00:00Z"))];
+        assert!(revision_as_of(revisions, at("2026-09-30T23:59:59Z")).is_none());
+        assert!(revision_as_of(Vec::new(), at("2026-10-01T00:00:00Z")).is_none());
+    }
+
+    #[test]
+    fn revision_as_of_ignores_missing_times() {
+        let revisions = vec![
+            revision("1", Some("2026-10-01T09:00:00Z")),
+            revision("2", None),
+        ];
+        let picked = revision_as_of(revisions, at("2026-10-13T00:00:00Z")).unwrap();
+        assert_eq!(picked.id.as_deref(), Some("1"));
+        assert!(revision_as_of(vec![revision("2", None)], at("2026-10-13T00:00:00Z")).is_none());
+    }
+}
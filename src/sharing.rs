@@ -0,0 +1,1107 @@
+use crate::{
+    cli::{GrantFilter, OutputFormat},
+    output::FilePrinter,
+    types::{SharingGrant, TDriveHub, TreeItem},
+    utils::{
+        FOLDER_MIME_TYPE, confirm, drive_names, get_about, is_not_found, paths_from_listings,
+        walk_tree, with_retry,
+    },
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt, future::ready, stream::BoxStream};
+use google_drive3::api::{File, Permission};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+/// Lists the permissions of `folder_id` and everything below it, one report
+/// row per grant. Each grant is classed as `public` (anyone with the link),
+/// `external` (a principal outside the account's domain and
+/// `internal_domains`) or `internal`, and marked as inherited from a folder
+/// above or granted directly on the item. Rows are printed as the walk goes.
+pub async fn audit_sharing(
+    hub: TDriveHub,
+    folder_id: &str,
+    format: &OutputFormat,
+    fields: &[String],
+    exposed_only: bool,
+    internal_domains: &[String],
+    concurrency: usize,
+) -> Result<()> {
+    let start_time = Instant::now();
+    let mut internal: HashSet<String> = internal_domains.iter().map(|d| d.to_lowercase()).collect();
+    let about = get_about(&hub).await?;
+    if let Some(email) = about.user.and_then(|user| user.email_address)
+        && let Some((_, domain)) = email.rsplit_once('@')
+    {
+        internal.insert(domain.to_lowercase());
+    }
+
+    eprintln!("Auditing sharing below folder {}", folder_id);
+    let retry_count = AtomicUsize::new(0);
+    // rows are printed as each item's permissions come in
+    let mut items = stream_grants(&hub, folder_id, concurrency, &retry_count);
+
+    let mut printer = FilePrinter::new(format, fields)?;
+    let (mut checked, mut failed) = (0, 0);
+    let (mut reported, mut public, mut external, mut inherited) = (0, 0, 0, 0);
+    let mut listing_error = None;
+    loop {
+        let item = match items.try_next().await {
+            Ok(Some(item)) => item,
+            Ok(None) => break,
+            Err(e) => {
+                listing_error = Some(e);
+                break;
+            }
+        };
+        let grants = match item.grants {
+            Ok(grants) => grants,
+            Err(e) => {
+                failed += 1;
+                eprintln!("✗ Failed to list permissions of {}: {}", item.path, e);
+                continue;
+            }
+        };
+        checked += 1;
+        for grant in grants {
+            let exposure = exposure_of(&grant.permission, &internal);
+            if exposed_only && exposure == "internal" {
+                continue;
+            }
+            reported += 1;
+            match exposure {
+                "public" => public += 1,
+                "external" => external += 1,
+                _ => {}
+            }
+            if grant.inherited_from.is_some() {
+                inherited += 1;
+            }
+
+            let mut record = permission_record(&grant);
+            record.insert("exposure".to_string(), exposure.into());
+            let record = fields
+                .iter()
+                .map(|field| {
+                    let value = record.remove(field).unwrap_or_default();
+                    (field.clone(), value)
+                })
+                .collect();
+            printer.print_record(&record)?;
+        }
+    }
+    printer.finish()?;
+
+    eprintln!("\nSharing Audit Summary:");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("Items checked: {}", checked);
+    eprintln!("Grants reported: {}", reported);
+    eprintln!("Public links: {}", public);
+    eprintln!("External principals: {}", external);
+    eprintln!("Inherited: {}", inherited);
+    eprintln!("Direct: {}", reported - inherited);
+    eprintln!("Failed items: {}", failed);
+    eprintln!("Retries: {}", retry_count.load(Ordering::Relaxed));
+    eprintln!("Total time: {:?}", start_time.elapsed());
+
+    if let Some(e) = listing_error {
+        return Err(e.context("Listing stopped early, the report is incomplete"));
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "The permissions of {} items could not be listed",
+            failed
+        ));
+    }
+    Ok(())
+}
+
+/// Removes the grants matching `filter` from `folder_id` and everything below
+/// it, or changes their role to `downgrade_to`. Ownership is never touched,
+/// and a downgrade only changes grants with more access than `downgrade_to`.
+/// Grants a shared drive marks as inherited are left to the folder they come
+/// from, where removing them takes effect for its contents. Every matching
+/// grant is logged to stdout with what happened to it, so the change can be
+/// reviewed.
+#[allow(clippy::too_many_arguments)]
+pub async fn unshare(
+    hub: TDriveHub,
+    folder_id: &str,
+    filter: &GrantFilter,
+    downgrade_to: Option<&str>,
+    format: &OutputFormat,
+    dry_run: bool,
+    yes: bool,
+    concurrency: usize,
+) -> Result<()> {
+    let start_time = Instant::now();
+    eprintln!("Finding grants to change below folder {}", folder_id);
+    let retry_count = AtomicUsize::new(0);
+    let (grants, list_failed) = collect_grants(&hub, folder_id, concurrency, &retry_count).await?;
+    if list_failed > 0 {
+        return Err(anyhow::anyhow!(
+            "The permissions of {} items could not be listed, nothing was changed",
+            list_failed
+        ));
+    }
+
+    // grants inherited within a shared drive can only change where they come
+    // from; the ones merely matching a parent folder are removed here too, as
+    // removing them twice just reports them gone
+    let (inherited, direct): (Vec<_>, Vec<_>) = grants
+        .into_iter()
+        .filter(|grant| is_unshare_target(grant, filter, downgrade_to))
+        .partition(|grant| inherited_in_drive(&grant.permission));
+
+    let (action, done) = match downgrade_to {
+        Some(_) => ("downgrade", "downgraded"),
+        None => ("remove", "removed"),
+    };
+    let fields = [
+        "path",
+        "id",
+        "permissionId",
+        "type",
+        "role",
+        "principal",
+        "inheritedFrom",
+        "action",
+        "newRole",
+        "status",
+        "error",
+    ]
+    .map(String::from);
+    let mut printer = FilePrinter::new(format, &fields)?;
+    let mut log = |grant: &SharingGrant, status: &str, error: String| {
+        let mut record = permission_record(grant);
+        record.insert("action".to_string(), action.into());
+        record.insert("newRole".to_string(), downgrade_to.into());
+        record.insert("status".to_string(), status.into());
+        record.insert("error".to_string(), error.into());
+        let record = fields
+            .iter()
+            .map(|field| {
+                let value = record.remove(field).unwrap_or_default();
+                (field.clone(), value)
+            })
+            .collect();
+        printer.print_record(&record)
+    };
+
+    for grant in &inherited {
+        log(grant, "inherited", String::new())?;
+    }
+    let items: HashSet<_> = direct.iter().map(|g| g.file_id.as_str()).collect();
+    let summary = format!(
+        "{} {} grants on {} items",
+        match downgrade_to {
+            Some(role) => format!("Change to {}", role),
+            None => "Remove".to_string(),
+        },
+        direct.len(),
+        items.len()
+    );
+    eprintln!("{}", summary);
+    if !inherited.is_empty() {
+        eprintln!(
+            "{} more matching grants are inherited within a shared drive and are left to the folders they come from",
+            inherited.len()
+        );
+    }
+    if dry_run {
+        for grant in &direct {
+            log(grant, "dry-run", String::new())?;
+        }
+        printer.finish()?;
+        return Ok(());
+    }
+    if direct.is_empty() {
+        printer.finish()?;
+        return Ok(());
+    }
+    if !yes && !confirm(&summary, false)? {
+        printer.finish()?;
+        eprintln!("Nothing changed");
+        return Ok(());
+    }
+
+    let pb = ProgressBar::new(direct.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("Unsharing [{bar:40.cyan/blue}] {pos}/{len} grants ({eta})")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    let mut results = futures::stream::iter(&direct)
+        .map(|grant| {
+            let hub = &hub;
+            let retries = &retry_count;
+            let pb = &pb;
+            async move {
+                let result = with_retry("Unshare", &grant.path, 3, retries, || {
+                    revoke_permission(hub, grant, downgrade_to)
+                })
+                .await;
+                pb.inc(1);
+                result
+            }
+        })
+        // keeps the log in walk order
+        .buffered(concurrency.max(1));
+
+    let (mut changed, mut gone, mut failed) = (0, 0, 0);
+    let mut grant = direct.iter();
+    while let Some(result) = results.next().await {
+        let grant = grant.next().unwrap();
+        match result {
+            Ok(true) => {
+                changed += 1;
+                pb.suspend(|| log(grant, done, String::new()))?;
+            }
+            Ok(false) => {
+                gone += 1;
+                pb.suspend(|| log(grant, "gone", String::new()))?;
+            }
+            Err(e) => {
+                failed += 1;
+                pb.suspend(|| {
+                    eprintln!(
+                        "✗ Failed to {} {} on {}: {}",
+                        action,
+                        principal_of(&grant.permission),
+                        grant.path,
+                        e
+                    );
+                    log(grant, "failed", e.to_string())
+                })?;
+            }
+        }
+    }
+    pb.finish_and_clear();
+    printer.finish()?;
+
+    eprintln!("\nUnshare Summary:");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!(
+        "{}: {}",
+        if downgrade_to.is_some() {
+            "Downgraded"
+        } else {
+            "Removed"
+        },
+        changed
+    );
+    eprintln!("Already gone: {}", gone);
+    eprintln!("Left to parent folders: {}", inherited.len());
+    eprintln!("Failed: {}", failed);
+    eprintln!("Retries: {}", retry_count.load(Ordering::Relaxed));
+    eprintln!("Total time: {:?}", start_time.elapsed());
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} grants could not be changed", failed));
+    }
+    Ok(())
+}
+
+/// Deletes a grant, or changes its role to `downgrade_to`. Returns false when
+/// the grant no longer exists, e.g. because an earlier attempt went through.
+async fn revoke_permission(
+    hub: &TDriveHub,
+    grant: &SharingGrant,
+    downgrade_to: Option<&str>,
+) -> Result<bool> {
+    let permission_id = grant.permission.id.as_deref().unwrap_or_default();
+    let result = match downgrade_to {
+        Some(role) => {
+            let permission = Permission {
+                role: Some(role.to_string()),
+                ..Default::default()
+            };
+            hub.permissions()
+                .update(permission, &grant.file_id, permission_id)
+                .supports_all_drives(true)
+                .add_scope(google_drive3::api::Scope::Full)
+                .doit()
+                .await
+                .map(|_| ())
+        }
+        None => hub
+            .permissions()
+            .delete(&grant.file_id, permission_id)
+            .supports_all_drives(true)
+            .add_scope(google_drive3::api::Scope::Full)
+            .doit()
+            .await
+            .map(|_| ()),
+    };
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if is_not_found(&e) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Roles below owner, from least to most access.
+const ROLES: [&str; 5] = [
+    "reader",
+    "commenter",
+    "writer",
+    "fileOrganizer",
+    "organizer",
+];
+
+/// Whether `unshare` acts on `grant`: it matches every filter, is not
+/// ownership, and when downgrading has more access than `downgrade_to`.
+fn is_unshare_target(
+    grant: &SharingGrant,
+    filter: &GrantFilter,
+    downgrade_to: Option<&str>,
+) -> bool {
+    let matches_any = |wanted: &[String], value: &str| {
+        wanted.is_empty() || wanted.iter().any(|w| w.eq_ignore_ascii_case(value))
+    };
+    let permission = &grant.permission;
+    let role = permission.role.as_deref().unwrap_or_default();
+    let rank = |role: &str| ROLES.iter().position(|r| *r == role);
+    let above_target = match downgrade_to {
+        Some(to) => matches!((rank(role), rank(to)), (Some(from), Some(to)) if from > to),
+        None => true,
+    };
+    role != "owner"
+        && above_target
+        && matches_any(
+            &filter.types,
+            permission.type_.as_deref().unwrap_or_default(),
+        )
+        && matches_any(&filter.roles, role)
+        && matches_any(&filter.domains, &domain_of(permission))
+        && matches_any(&filter.emails, &principal_of(permission))
+}
+
+/// Whether a shared drive reports `permission` as inherited, so it can't be
+/// changed on this item.
+fn inherited_in_drive(permission: &Permission) -> bool {
+    permission
+        .permission_details
+        .iter()
+        .flatten()
+        .any(|details| details.inherited == Some(true))
+}
+
+/// The grants found on one item during a sharing walk.
+struct ItemGrants {
+    path: String,
+    /// Fails when the item's permissions could not be listed
+    grants: Result<Vec<SharingGrant>>,
+}
+
+/// Walks `folder_id` and streams the permissions of it and every item below
+/// it as they are listed, `concurrency` items at a time. Items come in walk
+/// order (folders before their contents), so the grants a folder passes on
+/// are known by the time its contents are checked.
+fn stream_grants<'a>(
+    hub: &'a TDriveHub,
+    folder_id: &str,
+    concurrency: usize,
+    retry_count: &'a AtomicUsize,
+) -> BoxStream<'a, Result<ItemGrants>> {
+    let listings = walk_tree(
+        hub,
+        folder_id,
+        "id, name, mimeType",
+        None,
+        None,
+        concurrency,
+        None,
+    );
+    let root = TreeItem {
+        file: File {
+            id: Some(folder_id.to_string()),
+            mime_type: Some(FOLDER_MIME_TYPE.to_string()),
+            ..Default::default()
+        },
+        path: ".".to_string(),
+        parent_id: String::new(),
+    };
+    let mut seen = HashSet::from([folder_id.to_string()]);
+    let items = futures::stream::iter([Ok(root)]).chain(
+        paths_from_listings(listings, folder_id, drive_names)
+            // items with several parents are reported under the first path found
+            .try_filter(move |item| ready(seen.insert(item.file.id.clone().unwrap_or_default()))),
+    );
+
+    // (principal, role) -> the item the grant comes from, per folder
+    let mut folder_grants: HashMap<String, HashMap<(String, String), String>> = HashMap::new();
+    items
+        .map(move |item| async move {
+            let item = item?;
+            let id = item.file.id.clone().unwrap_or_default();
+            let permissions = with_retry("List permissions", &item.path, 3, retry_count, || {
+                get_permissions(hub, &id)
+            })
+            .await;
+            Ok((item, permissions))
+        })
+        // in order, so a folder's grants are known before its children's
+        .buffered(concurrency.max(1))
+        .map_ok(move |(item, permissions)| {
+            let id = item.file.id.unwrap_or_default();
+            let is_folder = item.file.mime_type.as_deref() == Some(FOLDER_MIME_TYPE);
+            let parent_grants = folder_grants.get(&item.parent_id);
+            let mut own_grants = HashMap::new();
+            let grants = permissions.map(|permissions| {
+                permissions
+                    .into_iter()
+                    .map(|permission| {
+                        let principal = principal_of(&permission);
+                        let role = permission.role.clone().unwrap_or_default();
+                        let inherited_from =
+                            inherited_from(&permission, &principal, &role, parent_grants);
+                        if is_folder && role != "owner" {
+                            let origin = inherited_from.clone().unwrap_or_else(|| id.clone());
+                            own_grants.insert((principal, role), origin);
+                        }
+                        SharingGrant {
+                            path: item.path.clone(),
+                            file_id: id.clone(),
+                            permission,
+                            inherited_from,
+                        }
+                    })
+                    .collect()
+            });
+            if is_folder && grants.is_ok() {
+                folder_grants.insert(id, own_grants);
+            }
+            ItemGrants {
+                path: item.path,
+                grants,
+            }
+        })
+        .boxed()
+}
+
+/// Collects every grant of [`stream_grants`], reporting items whose
+/// permissions could not be listed. Returns the grants and how many items
+/// failed.
+async fn collect_grants(
+    hub: &TDriveHub,
+    folder_id: &str,
+    concurrency: usize,
+    retry_count: &AtomicUsize,
+) -> Result<(Vec<SharingGrant>, usize)> {
+    let mut items = stream_grants(hub, folder_id, concurrency, retry_count);
+    let mut grants = Vec::new();
+    let mut failed = 0;
+    while let Some(item) = items.try_next().await? {
+        match item.grants {
+            Ok(found) => grants.extend(found),
+            Err(e) => {
+                failed += 1;
+                eprintln!("✗ Failed to list permissions of {}: {}", item.path, e);
+            }
+        }
+    }
+    Ok((grants, failed))
+}
+
+/// The report fields describing one grant.
+fn permission_record(grant: &SharingGrant) -> serde_json::Map<String, serde_json::Value> {
+    let permission = &grant.permission;
+    let serde_json::Value::Object(record) = serde_json::json!({
+        "path": grant.path,
+        "id": grant.file_id,
+        "permissionId": permission.id,
+        "type": permission.type_,
+        "role": permission.role,
+        "principal": principal_of(permission),
+        "displayName": permission.display_name,
+        "inherited": grant.inherited_from.is_some(),
+        "inheritedFrom": grant.inherited_from,
+        "expirationTime": permission.expiration_time.map(|t| t.to_rfc3339()),
+    }) else {
+        unreachable!()
+    };
+    record
+}
+
+/// The item a grant is inherited from, if it is inherited. Shared drives
+/// say so in `permissionDetails`; elsewhere a grant counts as inherited when
+/// the parent folder gives the same principal the same role. Ownership is
+/// never inherited.
+fn inherited_from(
+    permission: &Permission,
+    principal: &str,
+    role: &str,
+    parent_grants: Option<&HashMap<(String, String), String>>,
+) -> Option<String> {
+    if let Some(details) = &permission.permission_details
+        && !details.is_empty()
+    {
+        return details
+            .iter()
+            .find(|d| d.inherited == Some(true))
+            .map(|d| d.inherited_from.clone().unwrap_or_default());
+    }
+    if role == "owner" {
+        return None;
+    }
+    parent_grants?
+        .get(&(principal.to_string(), role.to_string()))
+        .cloned()
+}
+
+/// Classes a grant as `public`, `external` or `internal` to `internal`
+/// domains.
+fn exposure_of(permission: &Permission, internal: &HashSet<String>) -> &'static str {
+    if permission.type_.as_deref() == Some("anyone") {
+        return "public";
+    }
+    if internal.contains(&domain_of(permission)) {
+        "internal"
+    } else {
+        "external"
+    }
+}
+
+/// Grants the permissions listed in the CSV file `csv`, one per row:
+/// `target,principal,role[,expiration]`. The target is a file or folder ID,
+/// the principal an email address, `group:` and a group's address, a domain
+/// or `anyone`, and the optional expiration an RFC 3339 time or a date.
+/// Grants that already exist are left alone, so a re-run reports the same
+/// permissions as `exists`, unless their expiration differs, which is then
+/// updated. An `owner` row transfers ownership, which always notifies the new
+/// owner. The outcome of every row is printed to stdout as CSV.
+pub async fn share_from_csv(
+    hub: TDriveHub,
+    csv: &str,
+    notify: bool,
+    concurrency: usize,
+) -> Result<()> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .from_path(csv)?;
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |n: usize| record.get(n).unwrap_or_default().to_string();
+        if i == 0 && field(0).eq_ignore_ascii_case("target") {
+            continue;
+        }
+        rows.push([field(0), field(1), field(2), field(3)]);
+    }
+    eprintln!("Applying {} sharing rows from {}", rows.len(), csv);
+
+    let retry_count = AtomicUsize::new(0);
+    let mut report = csv::Writer::from_writer(std::io::stdout().lock());
+    report.write_record([
+        "target",
+        "principal",
+        "role",
+        "expiration",
+        "status",
+        "permission_id",
+        "error",
+    ])?;
+    let mut results = futures::stream::iter(&rows)
+        .map(|[target, principal, role, expiration]| {
+            let hub = &hub;
+            let retries = &retry_count;
+            async move {
+                let permission = match parse_grant(principal, role, expiration) {
+                    Ok(permission) => permission,
+                    Err(e) => return Err(e),
+                };
+                with_retry("Share", target, 3, retries, || {
+                    grant_permission(hub, target, permission.clone(), notify)
+                })
+                .await
+            }
+        })
+        // keeps the report in input order
+        .buffered(concurrency.max(1));
+
+    let (mut created, mut updated, mut existing, mut failed) = (0, 0, 0, 0);
+    let mut row = rows.iter();
+    while let Some(result) = results.next().await {
+        let [target, principal, role, expiration] = row.next().unwrap();
+        let (status, id, error) = match result {
+            Ok((id, status)) => {
+                match status {
+                    "created" => created += 1,
+                    "updated" => updated += 1,
+                    _ => existing += 1,
+                }
+                (status, id, String::new())
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("✗ Failed to share {} with {}: {}", target, principal, e);
+                ("failed", String::new(), e.to_string())
+            }
+        };
+        report.write_record([target, principal, role, expiration, status, &id, &error])?;
+        report.flush()?;
+    }
+
+    eprintln!(
+        "Created {}, expiration updated {}, already present {}, failed {} (retries: {})",
+        created,
+        updated,
+        existing,
+        failed,
+        retry_count.load(Ordering::Relaxed)
+    );
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} sharing rows failed", failed));
+    }
+    Ok(())
+}
+
+/// Builds the permission for one `share` row.
+fn parse_grant(principal: &str, role: &str, expiration: &str) -> Result<Permission> {
+    const ROLES: [&str; 6] = [
+        "reader",
+        "commenter",
+        "writer",
+        "fileOrganizer",
+        "organizer",
+        "owner",
+    ];
+    if !ROLES.contains(&role) {
+        return Err(anyhow::anyhow!(
+            "Unknown role '{}', expected one of {}",
+            role,
+            ROLES.join(", ")
+        ));
+    }
+    let mut permission = Permission {
+        role: Some(role.to_string()),
+        ..Default::default()
+    };
+    if principal.eq_ignore_ascii_case("anyone") {
+        permission.type_ = Some("anyone".to_string());
+    } else if let Some(group) = principal.strip_prefix("group:") {
+        if !group.contains('@') {
+            return Err(anyhow::anyhow!("'{}' is not a group address", group));
+        }
+        permission.type_ = Some("group".to_string());
+        permission.email_address = Some(group.to_string());
+    } else if principal.contains('@') {
+        permission.type_ = Some("user".to_string());
+        permission.email_address = Some(principal.to_string());
+    } else if principal.contains('.') {
+        permission.type_ = Some("domain".to_string());
+        permission.domain = Some(principal.to_string());
+    } else {
+        return Err(anyhow::anyhow!(
+            "'{}' is not an email address, group:address, a domain or 'anyone'",
+            principal
+        ));
+    }
+    if role == "owner" && permission.type_.as_deref() != Some("user") {
+        return Err(anyhow::anyhow!(
+            "Only a person can become owner, not '{}'",
+            principal
+        ));
+    }
+    if !expiration.is_empty() {
+        let expiration = DateTime::parse_from_rfc3339(expiration)
+            .map(|t| t.with_timezone(&Utc))
+            .or_else(|_| {
+                chrono::NaiveDate::parse_from_str(expiration, "%Y-%m-%d")
+                    .map(|d| d.and_hms_opt(23, 59, 59).unwrap().and_utc())
+            })
+            .map_err(|_| anyhow::anyhow!("Invalid expiration '{}'", expiration))?;
+        permission.expiration_time = Some(expiration);
+    }
+    Ok(permission)
+}
+
+/// Grants `permission` on `file_id` unless the same principal already has
+/// that role; an existing grant whose expiration differs is updated instead.
+/// Returns the permission ID and `created`, `updated` or `exists`.
+async fn grant_permission(
+    hub: &TDriveHub,
+    file_id: &str,
+    permission: Permission,
+    notify: bool,
+) -> Result<(String, &'static str)> {
+    let existing = get_permissions(hub, file_id).await?;
+    if let Some(found) = existing
+        .iter()
+        .find(|p| principal_of(p) == principal_of(&permission) && p.role == permission.role)
+    {
+        let id = found.id.clone().unwrap_or_default();
+        if found.expiration_time == permission.expiration_time {
+            return Ok((id, "exists"));
+        }
+        let change = Permission {
+            role: permission.role.clone(),
+            expiration_time: permission.expiration_time,
+            ..Default::default()
+        };
+        hub.permissions()
+            .update(change, file_id, &id)
+            .supports_all_drives(true)
+            .remove_expiration(permission.expiration_time.is_none())
+            .add_scope(google_drive3::api::Scope::Full)
+            .doit()
+            .await?;
+        return Ok((id, "updated"));
+    }
+
+    let mut request = hub
+        .permissions()
+        .create(permission.clone(), file_id)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full);
+    if permission.role.as_deref() == Some("owner") {
+        // Drive insists on telling the new owner
+        request = request
+            .transfer_ownership(true)
+            .send_notification_email(true);
+    } else if matches!(permission.type_.as_deref(), Some("user" | "group")) {
+        // Drive rejects the flag for links and domains, which get no email anyway
+        request = request.send_notification_email(notify);
+    }
+    let (_, created) = request.doit().await?;
+    Ok((created.id.unwrap_or_default(), "created"))
+}
+
+/// Lists every permission on a file or folder.
+async fn get_permissions(hub: &TDriveHub, file_id: &str) -> Result<Vec<Permission>> {
+    let mut permissions = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request = hub
+            .permissions()
+            .list(file_id)
+            .supports_all_drives(true)
+            .page_size(100)
+            .param(
+                "fields",
+                "nextPageToken, permissions(id, type, role, emailAddress, domain, \
+                 displayName, expirationTime, permissionDetails)",
+            )
+            .add_scope(google_drive3::api::Scope::Full);
+        if let Some(token) = &page_token {
+            request = request.page_token(token);
+        }
+        let (_, list) = request.doit().await?;
+        permissions.extend(list.permissions.unwrap_or_default());
+        page_token = list.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+    }
+    Ok(permissions)
+}
+
+/// The domain of a permission's principal; empty for `anyone`.
+fn domain_of(permission: &Permission) -> String {
+    let principal = principal_of(permission);
+    match permission.type_.as_deref() {
+        Some("anyone") => String::new(),
+        Some("domain") => principal,
+        _ => principal
+            .rsplit_once('@')
+            .map_or(String::new(), |(_, d)| d.to_string()),
+    }
+}
+
+/// Who a permission is for: an email address, a domain or `anyone`.
+fn principal_of(permission: &Permission) -> String {
+    match permission.type_.as_deref() {
+        Some("anyone") => "anyone".to_string(),
+        Some("domain") => permission.domain.clone().unwrap_or_default().to_lowercase(),
+        _ => permission
+            .email_address
+            .clone()
+            .unwrap_or_default()
+            .to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use google_drive3::api::PermissionPermissionDetails;
+
+    fn permission(type_: &str, principal: &str, role: &str) -> Permission {
+        let mut permission = Permission {
+            type_: Some(type_.to_string()),
+            role: Some(role.to_string()),
+            ..Default::default()
+        };
+        match type_ {
+            "domain" => permission.domain = Some(principal.to_string()),
+            "anyone" => {}
+            _ => permission.email_address = Some(principal.to_string()),
+        }
+        permission
+    }
+
+    #[test]
+    fn parse_grant_reads_each_kind_of_principal() {
+        let grant = parse_grant("Alice@Example.com", "writer", "").unwrap();
+        assert_eq!(grant.type_.as_deref(), Some("user"));
+        assert_eq!(grant.email_address.as_deref(), Some("Alice@Example.com"));
+        assert_eq!(grant.expiration_time, None);
+
+        let grant = parse_grant("group:team@example.com", "reader", "").unwrap();
+        assert_eq!(grant.type_.as_deref(), Some("group"));
+        assert_eq!(grant.email_address.as_deref(), Some("team@example.com"));
+
+        let grant = parse_grant("example.com", "reader", "2026-12-31").unwrap();
+        assert_eq!(grant.type_.as_deref(), Some("domain"));
+        assert_eq!(grant.domain.as_deref(), Some("example.com"));
+        assert_eq!(
+            grant.expiration_time.unwrap().to_rfc3339(),
+            "2026-12-31T23:59:59+00:00"
+        );
+
+        let grant = parse_grant("ANYONE", "reader", "2026-12-31T12:00:00Z").unwrap();
+        assert_eq!(grant.type_.as_deref(), Some("anyone"));
+        assert_eq!(
+            grant.expiration_time.unwrap().to_rfc3339(),
+            "2026-12-31T12:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn parse_grant_rejects_bad_rows() {
+        assert!(parse_grant("alice@example.com", "editor", "").is_err());
+        assert!(parse_grant("alice", "reader", "").is_err());
+        assert!(parse_grant("group:team", "reader", "").is_err());
+        assert!(parse_grant("alice@example.com", "reader", "next week").is_err());
+        assert!(parse_grant("example.com", "owner", "").is_err());
+        assert!(parse_grant("alice@example.com", "owner", "").is_ok());
+    }
+
+    #[test]
+    fn principal_and_domain_of_each_type() {
+        let user = permission("user", "Alice@Example.com", "writer");
+        assert_eq!(principal_of(&user), "alice@example.com");
+        assert_eq!(domain_of(&user), "example.com");
+
+        let group = permission("group", "team@lists.example.com", "reader");
+        assert_eq!(principal_of(&group), "team@lists.example.com");
+        assert_eq!(domain_of(&group), "lists.example.com");
+
+        let domain = permission("domain", "Partner.example", "reader");
+        assert_eq!(principal_of(&domain), "partner.example");
+        assert_eq!(domain_of(&domain), "partner.example");
+
+        let anyone = permission("anyone", "", "reader");
+        assert_eq!(principal_of(&anyone), "anyone");
+        assert_eq!(domain_of(&anyone), "");
+    }
+
+    #[test]
+    fn exposure_of_each_kind_of_grant() {
+        let internal = HashSet::from(["example.com".to_string()]);
+        let grant = permission("anyone", "", "reader");
+        assert_eq!(exposure_of(&grant, &internal), "public");
+        let grant = permission("user", "alice@example.com", "writer");
+        assert_eq!(exposure_of(&grant, &internal), "internal");
+        let grant = permission("domain", "example.com", "reader");
+        assert_eq!(exposure_of(&grant, &internal), "internal");
+        let grant = permission("group", "team@partner.example", "reader");
+        assert_eq!(exposure_of(&grant, &internal), "external");
+        let grant = permission("user", "bob@sub.example.com", "reader");
+        assert_eq!(exposure_of(&grant, &internal), "external");
+    }
+
+    #[test]
+    fn inherited_from_matches_parent_grants() {
+        let parent = HashMap::from([(
+            ("alice@example.com".to_string(), "writer".to_string()),
+            "top".to_string(),
+        )]);
+        let grant = permission("user", "alice@example.com", "writer");
+        assert_eq!(
+            inherited_from(&grant, "alice@example.com", "writer", Some(&parent)),
+            Some("top".to_string())
+        );
+        // a different role on the same principal is a direct grant
+        let grant = permission("user", "alice@example.com", "reader");
+        assert_eq!(
+            inherited_from(&grant, "alice@example.com", "reader", Some(&parent)),
+            None
+        );
+        assert_eq!(
+            inherited_from(&grant, "alice@example.com", "writer", None),
+            None
+        );
+        let parent = HashMap::from([(
+            ("alice@example.com".to_string(), "owner".to_string()),
+            "top".to_string(),
+        )]);
+        let grant = permission("user", "alice@example.com", "owner");
+        assert_eq!(
+            inherited_from(&grant, "alice@example.com", "owner", Some(&parent)),
+            None
+        );
+    }
+
+    #[test]
+    fn inherited_from_prefers_permission_details() {
+        let mut grant = permission("user", "alice@example.com", "writer");
+        grant.permission_details = Some(vec![PermissionPermissionDetails {
+            inherited: Some(true),
+            inherited_from: Some("drive".to_string()),
+            ..Default::default()
+        }]);
+        assert_eq!(
+            inherited_from(&grant, "alice@example.com", "writer", None),
+            Some("drive".to_string())
+        );
+        grant.permission_details = Some(vec![PermissionPermissionDetails {
+            inherited: Some(false),
+            ..Default::default()
+        }]);
+        let parent = HashMap::from([(
+            ("alice@example.com".to_string(), "writer".to_string()),
+            "top".to_string(),
+        )]);
+        assert_eq!(
+            inherited_from(&grant, "alice@example.com", "writer", Some(&parent)),
+            None
+        );
+    }
+
+    fn grant(type_: &str, principal: &str, role: &str) -> SharingGrant {
+        SharingGrant {
+            path: "./a".to_string(),
+            file_id: "a".to_string(),
+            permission: permission(type_, principal, role),
+            inherited_from: None,
+        }
+    }
+
+    fn filter(types: &[&str], roles: &[&str], domains: &[&str], emails: &[&str]) -> GrantFilter {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        GrantFilter {
+            types: strings(types),
+            roles: strings(roles),
+            domains: strings(domains),
+            emails: strings(emails),
+        }
+    }
+
+    #[test]
+    fn unshare_target_must_match_every_filter() {
+        let partner = filter(&["user"], &["writer"], &["Partner.example"], &[]);
+        assert!(is_unshare_target(
+            &grant("user", "bob@partner.example", "writer"),
+            &partner,
+            None
+        ));
+        assert!(!is_unshare_target(
+            &grant("user", "bob@partner.example", "reader"),
+            &partner,
+            None
+        ));
+        assert!(!is_unshare_target(
+            &grant("group", "team@partner.example", "writer"),
+            &partner,
+            None
+        ));
+        assert!(!is_unshare_target(
+            &grant("user", "bob@example.com", "writer"),
+            &partner,
+            None
+        ));
+
+        let bob = filter(&[], &[], &[], &["BOB@partner.example"]);
+        assert!(is_unshare_target(
+            &grant("user", "bob@partner.example", "reader"),
+            &bob,
+            None
+        ));
+        assert!(!is_unshare_target(
+            &grant("user", "bob@partner.example", "owner"),
+            &bob,
+            None
+        ));
+    }
+
+    #[test]
+    fn unshare_domain_matches_the_domain_and_addresses_in_it() {
+        let partner = filter(&[], &[], &["partner.example"], &[]);
+        for (type_, principal) in [
+            ("domain", "partner.example"),
+            ("user", "bob@partner.example"),
+            ("group", "team@partner.example"),
+        ] {
+            assert!(is_unshare_target(
+                &grant(type_, principal, "reader"),
+                &partner,
+                None
+            ));
+        }
+
+        let domain_only = filter(&["domain"], &[], &["partner.example"], &[]);
+        assert!(!is_unshare_target(
+            &grant("user", "bob@partner.example", "reader"),
+            &domain_only,
+            None
+        ));
+    }
+
+    #[test]
+    fn unshare_target_only_downgrades_higher_roles() {
+        let anyone = filter(&["anyone"], &[], &[], &[]);
+        let downgrade = Some("commenter");
+        assert!(is_unshare_target(
+            &grant("anyone", "", "writer"),
+            &anyone,
+            downgrade
+        ));
+        assert!(is_unshare_target(
+            &grant("anyone", "", "organizer"),
+            &anyone,
+            downgrade
+        ));
+        assert!(!is_unshare_target(
+            &grant("anyone", "", "commenter"),
+            &anyone,
+            downgrade
+        ));
+        assert!(!is_unshare_target(
+            &grant("anyone", "", "reader"),
+            &anyone,
+            downgrade
+        ));
+        assert!(is_unshare_target(
+            &grant("anyone", "", "reader"),
+            &anyone,
+            None
+        ));
+    }
+
+    #[test]
+    fn inherited_in_drive_reads_permission_details() {
+        let mut permission = permission("user", "alice@example.com", "writer");
+        assert!(!inherited_in_drive(&permission));
+        permission.permission_details = Some(vec![PermissionPermissionDetails {
+            inherited: Some(false),
+            ..Default::default()
+        }]);
+        assert!(!inherited_in_drive(&permission));
+        permission.permission_details = Some(vec![PermissionPermissionDetails {
+            inherited: Some(true),
+            ..Default::default()
+        }]);
+        assert!(inherited_in_drive(&permission));
+    }
+}
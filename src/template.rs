@@ -0,0 +1,265 @@
+use anyhow::Result;
+use google_drive3::api::File;
+use regex::{Captures, Regex};
+
+/// A new-name template for `rename`. Literal text is copied as is, and
+/// `{...}` placeholders are filled per file:
+///
+/// - `{1}`, `{ext}`: a capture group of the match, by number or by the name
+///   given in the pattern (`(?<ext>...)`), optionally transformed with
+///   `:upper`, `:lower` or `:title`
+/// - `{seq}`, `{seq:4}`: the running number of the file, optionally
+///   zero-padded to that many digits
+/// - `{createdTime}`, `{modifiedTime}`: the Drive timestamp, formatted with
+///   `%Y-%m-%d` or an explicit strftime format such as `{createdTime:%Y%m%d}`
+///
+/// `{{` and `}}` stand for literal braces.
+pub struct NameTemplate {
+    pieces: Vec<Piece>,
+}
+
+enum Piece {
+    Text(String),
+    Group(String, Case),
+    Seq(usize),
+    CreatedTime(String),
+    ModifiedTime(String),
+}
+
+enum Case {
+    Keep,
+    Upper,
+    Lower,
+    Title,
+}
+
+impl NameTemplate {
+    /// Parses `template`, checking every capture group against `pattern`.
+    pub fn parse(template: &str, pattern: &Regex) -> Result<Self> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => {
+                                return Err(anyhow::anyhow!("Unclosed '{{' in '{}'", template));
+                            }
+                        }
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(parse_placeholder(&placeholder, pattern)?);
+                }
+                '}' => return Err(anyhow::anyhow!("Unmatched '}}' in '{}'", template)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Self { pieces })
+    }
+
+    /// Builds the new name of `file` from the match `caps` of its old name.
+    pub fn render(&self, caps: &Captures, file: &File, seq: usize) -> String {
+        let mut name = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => name.push_str(text),
+                Piece::Group(group, case) => {
+                    let value = match group.parse::<usize>() {
+                        Ok(i) => caps.get(i),
+                        Err(_) => caps.name(group),
+                    }
+                    .map_or("", |m| m.as_str());
+                    name.push_str(&apply_case(value, case));
+                }
+                Piece::Seq(width) => name.push_str(&format!("{:0width$}", seq, width = width)),
+                Piece::CreatedTime(format) => {
+                    if let Some(t) = file.created_time {
+                        name.push_str(&t.format(format).to_string());
+                    }
+                }
+                Piece::ModifiedTime(format) => {
+                    if let Some(t) = file.modified_time {
+                        name.push_str(&t.format(format).to_string());
+                    }
+                }
+            }
+        }
+        name
+    }
+}
+
+fn parse_placeholder(placeholder: &str, pattern: &Regex) -> Result<Piece> {
+    let (key, arg) = match placeholder.split_once(':') {
+        Some((key, arg)) => (key, Some(arg)),
+        None => (placeholder, None),
+    };
+    let time_format = || {
+        let format = arg.unwrap_or("%Y-%m-%d").to_string();
+        // chrono only reports bad formats when rendering, so try one now
+        let mut probe = String::new();
+        std::fmt::Write::write_fmt(
+            &mut probe,
+            format_args!("{}", chrono::Utc::now().format(&format)),
+        )
+        .map_err(|_| anyhow::anyhow!("Invalid time format '{}'", format))?;
+        Ok::<_, anyhow::Error>(format)
+    };
+
+    match key {
+        "seq" => {
+            let width = match arg {
+                Some(width) => width
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid width in '{{{}}}'", placeholder))?,
+                None => 0,
+            };
+            Ok(Piece::Seq(width))
+        }
+        "createdTime" => Ok(Piece::CreatedTime(time_format()?)),
+        "modifiedTime" => Ok(Piece::ModifiedTime(time_format()?)),
+        group => {
+            let exists = match group.parse::<usize>() {
+                Ok(i) => i < pattern.captures_len(),
+                Err(_) => pattern.capture_names().flatten().any(|name| name == group),
+            };
+            if !exists {
+                return Err(anyhow::anyhow!(
+                    "Unknown placeholder '{{{}}}', the pattern has no such group",
+                    placeholder
+                ));
+            }
+            let case = match arg {
+                None => Case::Keep,
+                Some("upper") => Case::Upper,
+                Some("lower") => Case::Lower,
+                Some("title") => Case::Title,
+                Some(other) => {
+                    return Err(anyhow::anyhow!(
+                        "Unknown case '{}', expected upper, lower or title",
+                        other
+                    ));
+                }
+            };
+            Ok(Piece::Group(group.to_string(), case))
+        }
+    }
+}
+
+fn apply_case(value: &str, case: &Case) -> String {
+    match case {
+        Case::Keep => value.to_string(),
+        Case::Upper => value.to_uppercase(),
+        Case::Lower => value.to_lowercase(),
+        Case::Title => {
+            let mut word_start = true;
+            value
+                .chars()
+                .flat_map(|c| {
+                    let out: Vec<char> = match word_start {
+                        true => c.to_uppercase().collect(),
+                        false => c.to_lowercase().collect(),
+                    };
+                    word_start = !c.is_alphanumeric();
+                    out
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn render(template: &str, pattern: &str, name: &str, seq: usize) -> String {
+        let pattern = Regex::new(pattern).unwrap();
+        let file = File {
+            name: Some(name.to_string()),
+            created_time: Utc.with_ymd_and_hms(2026, 10, 13, 9, 30, 0).single(),
+            ..Default::default()
+        };
+        let caps = pattern.captures(name).unwrap();
+        NameTemplate::parse(template, &pattern)
+            .unwrap()
+            .render(&caps, &file, seq)
+    }
+
+    #[test]
+    fn fills_groups_seq_and_times() {
+        assert_eq!(
+            render(
+                "Invoice {createdTime} {1}.pdf",
+                r"^SCAN_(\d+)",
+                "SCAN_0001.pdf",
+                1
+            ),
+            "Invoice 2026-10-13 0001.pdf"
+        );
+        assert_eq!(
+            render(
+                "{seq:3}-{stem}.{ext:upper}",
+                r"(?P<stem>.+)\.(?P<ext>\w+)$",
+                "a.pdf",
+                7
+            ),
+            "007-a.PDF"
+        );
+        assert_eq!(
+            render("{createdTime:%Y%m%d}_{0}", r"^.*$", "x", 1),
+            "20261013_x"
+        );
+    }
+
+    #[test]
+    fn applies_case_and_literal_braces() {
+        assert_eq!(
+            render("{{{1:title}}}", r"^(.*)$", "hello wORLD-x", 1),
+            "{Hello World-X}"
+        );
+        assert_eq!(render("{1:lower}", r"^(.*)$", "ABC", 1), "abc");
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        let pattern = Regex::new(r"^(\d+)").unwrap();
+        for template in [
+            "{2}",
+            "{name}",
+            "{1:shout}",
+            "{seq:x}",
+            "open {1",
+            "close }",
+        ] {
+            assert!(
+                NameTemplate::parse(template, &pattern).is_err(),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn a_missing_optional_group_renders_empty() {
+        assert_eq!(render("a{2}b", r"^(x)(y)?", "x", 1), "ab");
+    }
+}
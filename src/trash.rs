@@ -0,0 +1,433 @@
+use crate::{
+    cli::{ListFilters, OutputFormat, SourceArgs},
+    output::{FilePrinter, format_size},
+    types::TDriveHub,
+    utils::{
+        FILE_FIELDS, FOLDER_MIME_TYPE, confirm, drive_filter_query, matches_local_filters,
+        query_files, stream_source_files, with_retry,
+    },
+};
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt, future::ready};
+use google_drive3::api::File;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+/// Moves the selected files to the trash, or deletes them for good with
+/// `permanent`. Folders go with everything in them. A preview with the count
+/// and total size is shown first and has to be confirmed unless `yes` is set.
+pub async fn remove_files(
+    hub: TDriveHub,
+    source: &SourceArgs,
+    filters: &ListFilters,
+    permanent: bool,
+    yes: bool,
+    max_concurrency: usize,
+) -> Result<()> {
+    let start_time = Instant::now();
+    let source = narrow_source(source, filters)?;
+    let files: Vec<File> = stream_source_files(
+        &hub,
+        &source,
+        FILE_FIELDS,
+        max_concurrency,
+        filters.order_by.as_deref(),
+    )?
+    .try_filter(|f| ready(matches_local_filters(f, filters)))
+    .take(filters.limit.unwrap_or(usize::MAX))
+    .try_collect()
+    .await?;
+
+    if files.is_empty() {
+        println!("No files found for the given source");
+        return Ok(());
+    }
+
+    let folders = files
+        .iter()
+        .filter(|f| f.mime_type.as_deref() == Some(FOLDER_MIME_TYPE))
+        .count();
+    let total_size: u64 = files
+        .iter()
+        .filter_map(|f| f.size.map(|s| s.max(0) as u64))
+        .sum();
+    for f in files.iter().take(10) {
+        println!(
+            "  {}  {}",
+            f.id.as_deref().unwrap_or_default(),
+            f.name.as_deref().unwrap_or_default()
+        );
+    }
+    if files.len() > 10 {
+        println!("  … and {} more", files.len() - 10);
+    }
+    let action = if permanent {
+        "Permanently delete"
+    } else {
+        "Move to the trash"
+    };
+    let summary = format!(
+        "{} {} files ({})",
+        action,
+        files.len(),
+        format_size(total_size)
+    );
+    if folders > 0 {
+        println!(
+            "{} of them are folders, everything inside them goes too",
+            folders
+        );
+    }
+    if !yes && !confirm(&summary, source.ids_from.as_deref() == Some("-"))? {
+        println!("Nothing removed");
+        return Ok(());
+    }
+
+    let multi_progress = MultiProgress::new();
+    let overall_progress = multi_progress.add(ProgressBar::new(files.len() as u64));
+    overall_progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Removing [{bar:40.cyan/blue}] {pos}/{len} files ({percent}%) | {elapsed_precise} | ETA: {eta_precise}")
+            .unwrap()
+            .progress_chars("█▓▒░ ")
+    );
+
+    let success_count = AtomicUsize::new(0);
+    let failure_count = AtomicUsize::new(0);
+    let retry_count = AtomicUsize::new(0);
+
+    futures::stream::iter(&files)
+        .map(|file| {
+            let hub = &hub;
+            let overall_prog = &overall_progress;
+            let (success, failure, retries) = (&success_count, &failure_count, &retry_count);
+            async move {
+                let id = file.id.as_deref().unwrap_or_default();
+                let name = file.name.as_deref().unwrap_or_default();
+                let result = match permanent {
+                    true => with_retry("Delete", name, 3, retries, || delete_file(hub, id)).await,
+                    false => with_retry("Trash", name, 3, retries, || trash_file(hub, id)).await,
+                };
+
+                overall_prog.inc(1);
+                overall_prog.set_message(name.to_string());
+                match &result {
+                    Ok(_) => success.fetch_add(1, Ordering::Relaxed),
+                    Err(_) => failure.fetch_add(1, Ordering::Relaxed),
+                };
+                (name, id, result)
+            }
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .for_each(|(name, id, result)| async move {
+            match result {
+                Ok(_) => println!("🗑 Removed '{}' ({})", name, id),
+                Err(e) => eprintln!("✗ Failed '{}' ({}): {}", name, id, e),
+            }
+        })
+        .await;
+
+    overall_progress.finish_with_message("Removal complete!");
+
+    let success = success_count.load(Ordering::Relaxed);
+    let failed = failure_count.load(Ordering::Relaxed);
+    println!("\nRemoval Summary:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!(
+        "{}: {}",
+        if permanent { "Deleted" } else { "Trashed" },
+        success
+    );
+    println!("Failed: {}", failed);
+    println!("Total retries: {}", retry_count.load(Ordering::Relaxed));
+    println!("Total time: {:?}", start_time.elapsed());
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} files could not be removed", failed));
+    }
+    Ok(())
+}
+
+/// Folds the filters Drive can apply into a folder or query source. ID lists
+/// are fetched one by one, so only the local filters work with them. Like
+/// every source query, the result leaves trashed files out when it is run.
+fn narrow_source(source: &SourceArgs, filters: &ListFilters) -> Result<SourceArgs> {
+    let Some(filter_query) = drive_filter_query(filters) else {
+        return Ok(source.clone());
+    };
+    let query = match (&source.folder_id, &source.query) {
+        (Some(folder_id), _) => format!("'{}' in parents and {}", folder_id, filter_query),
+        (None, Some(query)) => format!("({}) and {}", query, filter_query),
+        (None, None) => {
+            return Err(anyhow::anyhow!(
+                "--mime, --modified-after and --owner need a folder or query, not an ID list"
+            ));
+        }
+    };
+    Ok(SourceArgs {
+        folder_id: None,
+        query: Some(query),
+        ids_from: None,
+    })
+}
+
+/// Deletes a file for good, skipping the trash.
+async fn delete_file(hub: &TDriveHub, file_id: &str) -> Result<()> {
+    hub.files()
+        .delete(file_id)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    Ok(())
+}
+
+/// Lists trashed files the account can see, most recently trashed first.
+/// Drive cannot sort by `trashedTime`, so the whole trash is read and sorted
+/// here before anything is printed.
+pub async fn list_trash(
+    hub: TDriveHub,
+    format: &OutputFormat,
+    fields: &[String],
+    limit: Option<usize>,
+) -> Result<()> {
+    let mut api_fields = match format {
+        OutputFormat::Text => FILE_FIELDS.to_string(),
+        _ => fields.join(", "),
+    };
+    for field in ["trashedTime", "modifiedTime"] {
+        if !api_fields.split(", ").any(|f| f == field) {
+            api_fields = format!("{}, {}", field, api_fields);
+        }
+    }
+
+    let mut files: Vec<File> = query_files(hub, "trashed=true", &api_fields, None, false)
+        .try_collect()
+        .await?;
+    sort_by_trashed_time(&mut files);
+
+    let mut printer = FilePrinter::new(format, fields)?;
+    let mut count = 0;
+    for f in files.iter().take(limit.unwrap_or(usize::MAX)) {
+        if count == 0 && matches!(format, OutputFormat::Text) {
+            println!("Files in the trash:");
+        }
+        printer.print(f)?;
+        count += 1;
+    }
+    printer.finish()?;
+
+    if let OutputFormat::Text = format {
+        match count {
+            0 => println!("The trash is empty."),
+            n => println!("{} files", n),
+        }
+    }
+    Ok(())
+}
+
+/// Sorts files most recently trashed first. Files without a `trashedTime`
+/// fall back to their `modifiedTime`.
+fn sort_by_trashed_time(files: &mut [File]) {
+    files.sort_by_key(|f| std::cmp::Reverse(f.trashed_time.or(f.modified_time)));
+}
+
+/// Takes trashed files out of the trash: the given IDs, or every trashed
+/// file matching `query`. They go back to their original folder, or into
+/// `to` when given.
+pub async fn restore_files(
+    hub: TDriveHub,
+    ids: &[String],
+    query: Option<&str>,
+    to: Option<&str>,
+    concurrency: usize,
+) -> Result<()> {
+    let ids: Vec<String> = match query {
+        Some(query) => {
+            query_files(
+                Arc::clone(&hub),
+                &format!("({}) and trashed=true", query),
+                "id",
+                None,
+                false,
+            )
+            .map_ok(|f| f.id.unwrap_or_default())
+            .try_collect()
+            .await?
+        }
+        None => ids.to_vec(),
+    };
+    if ids.is_empty() {
+        println!("No trashed files match");
+        return Ok(());
+    }
+    println!("Restoring {} files", ids.len());
+
+    let retry_count = AtomicUsize::new(0);
+    let failed = futures::stream::iter(&ids)
+        .map(|id| {
+            let hub = &hub;
+            let retries = &retry_count;
+            async move {
+                let result =
+                    with_retry("Restore", id, 3, retries, || restore_file(hub, id, to)).await;
+                match &result {
+                    Ok(name) => println!("♻ Restored '{}' ({})", name, id),
+                    Err(e) => eprintln!("✗ Failed to restore {}: {}", id, e),
+                }
+                result.is_err()
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .filter(|failed| ready(*failed))
+        .count()
+        .await;
+
+    println!("Restored {} of {} files", ids.len() - failed, ids.len());
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} files could not be restored", failed));
+    }
+    Ok(())
+}
+
+/// Untrashes one file, moving it into `to` if given, and returns its name.
+async fn restore_file(hub: &TDriveHub, file_id: &str, to: Option<&str>) -> Result<String> {
+    let update = File {
+        trashed: Some(false),
+        ..Default::default()
+    };
+    let mut request = hub
+        .files()
+        .update(update, file_id)
+        .supports_all_drives(true)
+        .param("fields", "name")
+        .add_scope(google_drive3::api::Scope::Full);
+    if let Some(to) = to {
+        let (_, current) = hub
+            .files()
+            .get(file_id)
+            .supports_all_drives(true)
+            .param("fields", "parents")
+            .add_scope(google_drive3::api::Scope::Full)
+            .doit()
+            .await?;
+        request = request.add_parents(to);
+        if let Some(parents) = current.parents.filter(|p| !p.is_empty()) {
+            request = request.remove_parents(&parents.join(","));
+        }
+    }
+    let (_, file) = request.doit_without_upload().await?;
+    Ok(file.name.unwrap_or_default())
+}
+
+/// Permanently deletes everything in the trash of My Drive, or of the shared
+/// drive `drive_id`, after confirmation unless `yes` is set.
+pub async fn empty_trash(hub: TDriveHub, drive_id: Option<&str>, yes: bool) -> Result<()> {
+    let whose = match drive_id {
+        Some(drive_id) => format!("shared drive {}", drive_id),
+        None => "My Drive".to_string(),
+    };
+    let question = format!("Permanently delete everything in the trash of {}", whose);
+    if !yes && !confirm(&question, false)? {
+        println!("Trash left as it is");
+        return Ok(());
+    }
+
+    let mut request = hub
+        .files()
+        .empty_trash()
+        .add_scope(google_drive3::api::Scope::Full);
+    if let Some(drive_id) = drive_id {
+        request = request.drive_id(drive_id);
+    }
+    request.doit().await?;
+    println!("Emptied the trash of {}", whose);
+    Ok(())
+}
+
+/// Moves a file to the Drive trash, from where it can still be restored.
+pub async fn trash_file(hub: &TDriveHub, file_id: &str) -> Result<()> {
+    let update = File {
+        trashed: Some(true),
+        ..Default::default()
+    };
+    hub.files()
+        .update(update, file_id)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit_without_upload()
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[test]
+    fn narrow_source_folds_drive_filters_into_the_query() {
+        let filters = ListFilters {
+            mime: Some("image/*".to_string()),
+            name_glob: None,
+            min_size: None,
+            modified_after: None,
+            owner: None,
+            order_by: None,
+            limit: None,
+        };
+        let folder = SourceArgs {
+            folder_id: Some("F1".to_string()),
+            query: None,
+            ids_from: None,
+        };
+        let narrowed = narrow_source(&folder, &filters).unwrap();
+        assert_eq!(narrowed.folder_id, None);
+        assert_eq!(
+            narrowed.query.as_deref(),
+            Some("'F1' in parents and mimeType contains 'image/'")
+        );
+
+        let query = SourceArgs {
+            folder_id: None,
+            query: Some("starred".to_string()),
+            ids_from: None,
+        };
+        let narrowed = narrow_source(&query, &filters).unwrap();
+        assert_eq!(
+            narrowed.query.as_deref(),
+            Some("(starred) and mimeType contains 'image/'")
+        );
+
+        let ids = SourceArgs {
+            folder_id: None,
+            query: None,
+            ids_from: Some("-".to_string()),
+        };
+        assert!(narrow_source(&ids, &filters).is_err());
+    }
+
+    #[test]
+    fn sorts_by_trashed_time_not_modified_time() {
+        let file = |id: &str, trashed: Option<i64>, modified: i64| File {
+            id: Some(id.to_string()),
+            trashed_time: trashed.and_then(|t| DateTime::from_timestamp(t, 0)),
+            modified_time: DateTime::from_timestamp(modified, 0),
+            ..Default::default()
+        };
+        let mut files = vec![
+            file("old", Some(100), 900),
+            file("new", Some(300), 50),
+            file("unknown", None, 200),
+        ];
+        sort_by_trashed_time(&mut files);
+        let ids: Vec<_> = files.iter().map(|f| f.id.as_deref().unwrap()).collect();
+        assert_eq!(ids, ["new", "unknown", "old"]);
+    }
+}
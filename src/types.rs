@@ -1,6 +1,47 @@
-use google_drive3::{DriveHub, hyper_rustls, hyper_util};
+use google_drive3::{
+    DriveHub,
+    api::{File, Permission},
+    hyper_rustls, hyper_util,
+};
 use hyper_rustls::HttpsConnector;
 use hyper_util::client::legacy::connect::HttpConnector;
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 pub type TDriveHub = Arc<DriveHub<HttpsConnector<HttpConnector>>>;
+
+/// A Drive file paired with the name (or archive path) it is saved under
+/// locally, optionally pinned to one revision of its content.
+pub struct DownloadTarget {
+    pub file: File,
+    pub local_name: String,
+    pub revision_id: Option<String>,
+}
+
+/// Drive names shared by several files in one folder, each mapped to the
+/// `(drive_id, local_name)` pairs they were saved under.
+pub type NameCollisions = BTreeMap<String, Vec<(String, String)>>;
+
+/// The direct children of one folder found during a recursive walk.
+pub struct FolderListing {
+    pub folder_id: String,
+    pub children: Vec<File>,
+}
+
+/// One item found during a recursive walk, with its place in the tree.
+pub struct TreeItem {
+    pub file: File,
+    /// Relative to the folder the walk started from, e.g. `2024/q1.pdf`
+    pub path: String,
+    /// The folder it was listed in
+    pub parent_id: String,
+}
+
+/// One permission found on an item during a sharing walk.
+pub struct SharingGrant {
+    /// Relative to the folder the walk started from, `.` for that folder
+    pub path: String,
+    pub file_id: String,
+    pub permission: Permission,
+    /// The item the grant comes from, if it is inherited
+    pub inherited_from: Option<String>,
+}
@@ -1,11 +1,18 @@
-use crate::types::TDriveHub;
+use crate::{
+    cli::{ListFilters, OutputFormat, SourceArgs},
+    output::{FilePrinter, format_size},
+    types::{FolderListing, TDriveHub, TreeItem},
+};
 use anyhow::Result;
-use futures::StreamExt;
-use google_drive3::api::File;
-use http_body_util::BodyExt;
+use futures::{
+    StreamExt, TryStreamExt,
+    future::ready,
+    stream::{BoxStream, FuturesUnordered},
+};
+use google_drive3::api::{About, File};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File as FsFile,
     io::Write,
     path::Path,
@@ -17,51 +24,416 @@ use std::{
 };
 use tokio::time::sleep;
 
-pub async fn list_files(hub: TDriveHub, folder_id: &String) -> Result<()> {
-    let files = get_files(hub, folder_id).await?;
-    if files.is_empty() {
-        println!("No files found in that folder (visible to this service account).");
+#[allow(clippy::too_many_arguments)]
+pub async fn list_files(
+    hub: TDriveHub,
+    folder_id: &String,
+    format: &OutputFormat,
+    fields: &[String],
+    recursive: bool,
+    depth: Option<usize>,
+    concurrency: usize,
+    filters: &ListFilters,
+) -> Result<()> {
+    let mut api_fields = match format {
+        OutputFormat::Text => FILE_FIELDS.to_string(),
+        _ => fields.join(", "),
+    };
+    // the walk itself needs to recognise folders, and the local filters
+    // need names and sizes
+    let needed = [
+        ("id", recursive),
+        ("mimeType", recursive),
+        ("name", filters.name_glob.is_some()),
+        ("size", filters.min_size.is_some()),
+    ];
+    for (field, wanted) in needed {
+        if wanted && !api_fields.split(", ").any(|f| f == field) {
+            api_fields = format!("{}, {}", field, api_fields);
+        }
+    }
+    let filter_query = drive_filter_query(filters);
+    let order_by = filters.order_by.as_deref();
+    let limit = filters.limit.unwrap_or(usize::MAX);
+
+    let files = if recursive {
+        // folders come back regardless of the Drive-side filters so the walk
+        // can continue, but are only shown when asked for by type
+        let show_folders =
+            filter_query.is_none() || filters.mime.as_deref() == Some(FOLDER_MIME_TYPE);
+        walk_tree(
+            &hub,
+            folder_id,
+            &api_fields,
+            filter_query.as_deref(),
+            order_by,
+            concurrency,
+            depth,
+        )
+        .map_ok(|listing| futures::stream::iter(listing.children.into_iter().map(Ok)))
+        .try_flatten()
+        .try_filter(move |f| {
+            ready(show_folders || f.mime_type.as_deref() != Some(FOLDER_MIME_TYPE))
+        })
+        .boxed()
     } else {
-        println!("Files in folder {}:", folder_id);
-        println!("{}", &files.len());
-        for f in files {
-            println!(
-                "- {} ({})",
-                f.name.unwrap_or_default(),
-                f.mime_type.unwrap_or_default()
-            );
+        let mut query = format!("'{}' in parents and trashed=false", folder_id);
+        if let Some(filter_query) = &filter_query {
+            query = format!("{} and {}", query, filter_query);
+        }
+        query_files(hub, &query, &api_fields, order_by, false)
+    };
+    // printed as they arrive, without holding the folder in memory; no more
+    // pages or folders are read once the limit is reached
+    let mut files = files
+        .try_filter(|f| ready(matches_local_filters(f, filters)))
+        .take(limit);
+
+    let mut printer = FilePrinter::new(format, fields)?;
+    let mut count = 0;
+    while let Some(f) = files.try_next().await? {
+        if count == 0 && matches!(format, OutputFormat::Text) {
+            println!("Files in folder {}:", folder_id);
+        }
+        printer.print(&f)?;
+        count += 1;
+    }
+    printer.finish()?;
+
+    if let OutputFormat::Text = format {
+        if count == 0 {
+            println!("No files found in that folder (visible to this service account).");
+        } else {
+            println!("{} files", count);
         }
     }
     Ok(())
 }
 
-pub async fn get_files(hub: TDriveHub, folder_id: &String) -> Result<Vec<File>> {
-    let mut all_files = Vec::new();
-    let mut page_token: Option<String> = None;
-    loop {
-        let mut request = hub
-            .files()
-            .list()
-            .q(&format!("'{}' in parents and trashed=false", &folder_id))
-            // .corpora("allDrives")
-            .include_items_from_all_drives(true)
-            .supports_all_drives(true)
-            .page_size(1000)
-            .param("fields", "nextPageToken, files(id, name, mimeType, size)")
-            .add_scope(google_drive3::api::Scope::Full);
-        if let Some(token) = page_token {
-            request = request.page_token(&token);
+pub const FOLDER_MIME_TYPE: &str = "application/vnd.google-apps.folder";
+
+/// Prefix of the MIME types of native Google files (Docs, Sheets, Slides, …),
+/// which have no binary content of their own.
+pub const NATIVE_MIME_PREFIX: &str = "application/vnd.google-apps.";
+
+/// File fields requested from Drive wherever whole `File`s are fetched.
+/// `parents` is left out, as it is costly for Drive to fill in; commands that
+/// need it ask for it on top.
+pub const FILE_FIELDS: &str = "id, name, mimeType, size, md5Checksum, createdTime, modifiedTime";
+
+/// Builds the Drive `q` clauses for the filters Drive can apply itself.
+pub fn drive_filter_query(filters: &ListFilters) -> Option<String> {
+    let mut clauses = Vec::new();
+    if let Some(mime) = &filters.mime {
+        clauses.push(match mime.strip_suffix('*') {
+            Some(family) => format!("mimeType contains '{}'", escape_query(family)),
+            None => format!("mimeType = '{}'", escape_query(mime)),
+        });
+    }
+    if let Some(after) = filters.modified_after {
+        clauses.push(format!(
+            "modifiedTime > '{}'",
+            after.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        ));
+    }
+    if let Some(owner) = &filters.owner {
+        clauses.push(format!("'{}' in owners", escape_query(owner)));
+    }
+    (!clauses.is_empty()).then(|| clauses.join(" and "))
+}
+
+/// Checks the filters Drive cannot apply in a query: name glob and size.
+pub fn matches_local_filters(file: &File, filters: &ListFilters) -> bool {
+    let name_ok = filters
+        .name_glob
+        .as_ref()
+        .is_none_or(|glob| glob.matches(file.name.as_deref().unwrap_or_default()));
+    let size_ok = filters
+        .min_size
+        .is_none_or(|min| file.size.is_some_and(|size| size.max(0) as u64 >= min));
+    name_ok && size_ok
+}
+
+/// Escapes a value for use inside a quoted string in a Drive query.
+pub fn escape_query(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// Streams the direct children of a folder, fetching only `fields` of each file.
+pub fn get_files(
+    hub: TDriveHub,
+    folder_id: &str,
+    fields: &str,
+) -> BoxStream<'static, Result<File>> {
+    query_files(
+        hub,
+        &format!("'{}' in parents and trashed=false", folder_id),
+        fields,
+        None,
+        false,
+    )
+}
+
+/// Runs an arbitrary Drive search (`q` syntax), optionally sorted by a Drive
+/// `orderBy` expression. With `all_drives` the search covers every shared
+/// drive the account belongs to, not just My Drive and items shared with it.
+/// Pages are requested lazily, one at a time, and files are yielded as soon
+/// as their page arrives.
+pub fn query_files(
+    hub: TDriveHub,
+    query: &str,
+    fields: &str,
+    order_by: Option<&str>,
+    all_drives: bool,
+) -> BoxStream<'static, Result<File>> {
+    let query = query.to_string();
+    let fields = format!("nextPageToken, files({})", fields);
+    let order_by = order_by.map(str::to_string);
+
+    // `None` once the last page has been fetched
+    futures::stream::try_unfold(Some(None), move |page_token: Option<Option<String>>| {
+        let hub = Arc::clone(&hub);
+        let query = query.clone();
+        let fields = fields.clone();
+        let order_by = order_by.clone();
+        async move {
+            let Some(page_token) = page_token else {
+                return Ok(None);
+            };
+            let mut request = hub
+                .files()
+                .list()
+                .q(&query)
+                .include_items_from_all_drives(true)
+                .supports_all_drives(true)
+                .page_size(1000)
+                .param("fields", &fields)
+                .add_scope(google_drive3::api::Scope::Full);
+            if all_drives {
+                request = request.corpora("allDrives");
+            }
+            if let Some(order_by) = &order_by {
+                request = request.order_by(order_by);
+            }
+            if let Some(token) = page_token {
+                request = request.page_token(&token);
+            }
+            let (_, file_list) = request.doit().await?;
+            let page = file_list.files.unwrap_or_default();
+            Ok::<_, anyhow::Error>(Some((page, file_list.next_page_token.map(Some))))
         }
-        let (_, file_list) = request.doit().await?;
-        if let Some(files) = file_list.files {
-            all_files.extend(files);
+    })
+    .map_ok(|page| futures::stream::iter(page.into_iter().map(Ok)))
+    .try_flatten()
+    .boxed()
+}
+
+/// Streams the files selected by a folder, a query or an ID list, fetching
+/// only `fields` of each. Folder and query results come sorted by `order_by`;
+/// ID lists keep their own order.
+pub fn stream_source_files(
+    hub: &TDriveHub,
+    source: &SourceArgs,
+    fields: &str,
+    concurrency: usize,
+    order_by: Option<&str>,
+) -> Result<BoxStream<'static, Result<File>>> {
+    if let Some(query) = source_query(source) {
+        match &source.folder_id {
+            Some(folder_id) => eprintln!("Source folder ID: {}", folder_id),
+            None => eprintln!("Source query: {}", query),
         }
-        page_token = file_list.next_page_token;
-        if page_token.is_none() {
-            break;
+        Ok(query_files(
+            Arc::clone(hub),
+            &query,
+            fields,
+            order_by,
+            false,
+        ))
+    } else if let Some(ids_from) = &source.ids_from {
+        let ids = read_ids(ids_from)?;
+        eprintln!("Source: {} file IDs from {}", ids.len(), ids_from);
+        Ok(get_files_by_id(hub, ids, fields, concurrency))
+    } else {
+        Err(anyhow::anyhow!("No folder, query or ID list given"))
+    }
+}
+
+/// The Drive query for a folder or query source. Trashed files are always
+/// left out, so a query never reaches what is already in the trash.
+fn source_query(source: &SourceArgs) -> Option<String> {
+    if let Some(folder_id) = &source.folder_id {
+        Some(format!("'{}' in parents and trashed=false", folder_id))
+    } else {
+        source
+            .query
+            .as_ref()
+            .map(|query| format!("({}) and trashed=false", query))
+    }
+}
+
+/// Reads Drive file IDs, one per line, from a file or stdin (`-`). Only the
+/// first whitespace- or comma-separated field of each line is used, and lines
+/// that do not look like an ID (blank lines, comments, headers, the `- name`
+/// lines of the text listing) are skipped.
+fn read_ids(path: &str) -> Result<Vec<String>> {
+    let text = if path == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    Ok(parse_ids(&text))
+}
+
+fn parse_ids(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.split([' ', '\t', ',']).find(|f| !f.is_empty()))
+        .filter(|id| is_drive_id(id))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Drive IDs are at least 19 characters (shared drives) and use only letters,
+/// digits, `-` and `_`.
+fn is_drive_id(token: &str) -> bool {
+    token.len() >= 19
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Looks up `fields` of each of `ids`, `concurrency` at a time, yielding
+/// files in the order of `ids`. Lookups are retried; IDs that do not exist
+/// are reported and skipped, any other failure is yielded as an error.
+fn get_files_by_id(
+    hub: &TDriveHub,
+    ids: Vec<String>,
+    fields: &str,
+    concurrency: usize,
+) -> BoxStream<'static, Result<File>> {
+    let hub = Arc::clone(hub);
+    let fields = fields.to_string();
+    let retry_count = Arc::new(AtomicUsize::new(0));
+    futures::stream::iter(ids)
+        .map(move |id| {
+            let hub = Arc::clone(&hub);
+            let fields = fields.clone();
+            let retry_count = Arc::clone(&retry_count);
+            async move {
+                let result = with_retry("Look up", &id, 3, &retry_count, || async {
+                    match hub
+                        .files()
+                        .get(&id)
+                        .supports_all_drives(true)
+                        .param("fields", &fields)
+                        .add_scope(google_drive3::api::Scope::Full)
+                        .doit()
+                        .await
+                    {
+                        Ok((_, file)) => Ok(Some(file)),
+                        Err(e) if is_not_found(&e) => Ok(None),
+                        Err(e) => Err(e.into()),
+                    }
+                })
+                .await;
+                (id, result)
+            }
+        })
+        .buffered(concurrency.max(1))
+        .filter_map(|(id, result)| {
+            ready(match result {
+                Ok(Some(file)) => Some(Ok(file)),
+                Ok(None) => {
+                    eprintln!("No file with ID '{}', skipping it", id);
+                    None
+                }
+                Err(e) => Some(Err(e.context(format!("Failed to look up '{}'", id)))),
+            })
+        })
+        .boxed()
+}
+
+/// Prints the authenticated account, its storage quota and the largest
+/// allowed upload.
+pub async fn show_about(hub: TDriveHub) -> Result<()> {
+    let about = get_about(&hub).await?;
+    let user = about.user.unwrap_or_default();
+    let quota = about.storage_quota.unwrap_or_default();
+    let size = |bytes: Option<i64>| {
+        bytes
+            .map(|b| format_size(b.max(0) as u64))
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+
+    println!(
+        "User: {} <{}>",
+        user.display_name.unwrap_or_default(),
+        user.email_address.unwrap_or_default()
+    );
+    match quota.limit {
+        Some(limit) => {
+            let usage = quota.usage.unwrap_or(0);
+            println!("Storage limit: {}", size(Some(limit)));
+            println!(
+                "Usage: {} ({:.1}%)",
+                size(Some(usage)),
+                usage as f64 * 100.0 / limit.max(1) as f64
+            );
+            println!("Available: {}", size(Some(limit - usage)));
+        }
+        None => {
+            println!("Storage limit: unlimited");
+            println!("Usage: {}", size(quota.usage));
+        }
+    }
+    println!("Usage in Drive: {}", size(quota.usage_in_drive));
+    println!("Usage in trash: {}", size(quota.usage_in_drive_trash));
+    println!("Max upload size: {}", size(about.max_upload_size));
+    Ok(())
+}
+
+pub async fn get_about(hub: &TDriveHub) -> Result<About> {
+    let (_, about) = hub
+        .about()
+        .get()
+        .param(
+            "fields",
+            "user(displayName, emailAddress), storageQuota, maxUploadSize",
+        )
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    Ok(about)
+}
+
+/// Warns when `total_size` more bytes would not fit in the remaining storage
+/// quota. Shared drives have their own storage, so uploads into them are not
+/// checked.
+async fn check_upload_quota(hub: &TDriveHub, folder_id: &str, total_size: u64) -> Result<()> {
+    let (_, folder) = hub
+        .files()
+        .get(folder_id)
+        .supports_all_drives(true)
+        .param("fields", "driveId")
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    if folder.drive_id.is_some() {
+        return Ok(());
+    }
+
+    let quota = get_about(hub).await?.storage_quota.unwrap_or_default();
+    if let Some(limit) = quota.limit {
+        let available = (limit - quota.usage.unwrap_or(0)).max(0) as u64;
+        if total_size > available {
+            println!(
+                "⚠ Warning: {} to upload but only {} of storage quota left, expect storageQuotaExceeded errors",
+                format_size(total_size),
+                format_size(available)
+            );
         }
     }
-    Ok(all_files)
+    Ok(())
 }
 
 pub async fn upload(
@@ -97,6 +469,10 @@ pub async fn upload(
         total_size as f64 / (1024.0 * 1024.0 * 1024.0)
     );
 
+    if let Err(e) = check_upload_quota(&hub, folder_id, total_size).await {
+        println!("Could not check storage quota: {}", e);
+    }
+
     let optimal_concurrency = calculate_optimal_concurrency(&entries, *concurrency);
     println!("Using {} concurrent uploads", optimal_concurrency);
 
@@ -199,8 +575,11 @@ pub async fn upload(
 
 async fn get_existing_files(hub: &TDriveHub, folder_id: &str) -> Result<HashSet<String>> {
     println!("Checking for existing files...");
-    let files = get_files(Arc::clone(hub), &folder_id.to_string()).await?;
-    let existing: HashSet<String> = files.into_iter().filter_map(|f| f.name).collect();
+    let mut files = get_files(Arc::clone(hub), folder_id, "name");
+    let mut existing = HashSet::new();
+    while let Some(f) = files.try_next().await? {
+        existing.extend(f.name);
+    }
 
     if !existing.is_empty() {
         println!(
@@ -288,12 +667,31 @@ async fn upload_file_with_retry(
     max_retries: u32,
     retry_counter: &Arc<AtomicUsize>,
 ) -> Result<String> {
+    with_retry("Upload", fname, max_retries, retry_counter, || {
+        upload_single_file(hub, path, fname, folder_id)
+    })
+    .await
+}
+
+/// Runs `op` until it succeeds or `max_retries` retries are used up, backing
+/// off exponentially and waiting longer when Drive reports a rate limit.
+pub async fn with_retry<T, F, Fut>(
+    action: &str,
+    name: &str,
+    max_retries: u32,
+    retry_counter: &AtomicUsize,
+    mut op: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
     let mut attempt = 0;
     let mut backoff = Duration::from_secs(1);
 
     loop {
-        match upload_single_file(hub, path, fname, folder_id).await {
-            Ok(file_id) => return Ok(file_id),
+        match op().await {
+            Ok(value) => return Ok(value),
             Err(e) if attempt < max_retries => {
                 attempt += 1;
                 retry_counter.fetch_add(1, Ordering::Relaxed);
@@ -306,12 +704,12 @@ async fn upload_file_with_retry(
                     backoff = Duration::from_secs(10 * attempt as u64);
                     eprintln!(
                         "Rate limit hit for '{}', waiting {:?} before retry {}/{}",
-                        fname, backoff, attempt, max_retries
+                        name, backoff, attempt, max_retries
                     );
                 } else {
                     eprintln!(
-                        "Upload failed for '{}': {}, retrying {}/{} in {:?}",
-                        fname, e, attempt, max_retries, backoff
+                        "{} failed for '{}': {}, retrying {}/{} in {:?}",
+                        action, name, e, attempt, max_retries, backoff
                     );
                 }
 
@@ -324,6 +722,16 @@ async fn upload_file_with_retry(
     }
 }
 
+/// Whether Drive answered that the item does not exist, or is not visible to
+/// this account.
+pub fn is_not_found(e: &google_drive3::Error) -> bool {
+    match e {
+        google_drive3::Error::BadRequest(body) => body["error"]["code"] == 404,
+        google_drive3::Error::Failure(response) => response.status().as_u16() == 404,
+        _ => false,
+    }
+}
+
 async fn upload_single_file(
     hub: &TDriveHub,
     path: &Path,
@@ -373,326 +781,447 @@ fn get_mime_type(path: &Path) -> &'static str {
     }
 }
 
-pub async fn download_files(
-    hub: TDriveHub,
-    folder_id: &String,
-    path: &String,
-    max_concurrency: usize,
-) -> Result<()> {
-    let start_time = Instant::now();
-    println!("Starting bulk download from Google Drive");
-    println!("Source folder ID: {}", folder_id);
-    println!("Target directory: {}", path);
-
-    std::fs::create_dir_all(path)?;
-
-    let files = get_files(Arc::clone(&hub), folder_id).await?;
-
-    if files.is_empty() {
-        println!("No files found in the specified folder");
-        return Ok(());
-    }
-
-    // Filter out folders
-    let files_to_download: Vec<_> = files
-        .into_iter()
-        .filter(|f| {
-            f.mime_type
-                .as_ref()
-                .map(|m| m != "application/vnd.google-apps.folder")
-                .unwrap_or(true)
-        })
-        .collect();
-
-    // download onlly new files
-    let existing_files = get_existing_local_files(path)?;
-    let new_files: Vec<_> = files_to_download
-        .into_iter()
-        .filter(|f| {
-            f.name
-                .as_ref()
-                .map(|name| !existing_files.contains(name))
-                .unwrap_or(false)
-        })
-        .collect();
-
-    if new_files.is_empty() {
-        println!("All files already exist locally. No downloads needed.");
-        return Ok(());
-    }
-
-    println!("Found {} files to download", new_files.len());
-
-    let total_size: i64 = new_files.iter().filter_map(|f| f.size).sum();
-    if total_size > 0 {
-        println!(
-            "Total size to download: {:.2} GB",
-            total_size as f64 / (1024.0 * 1024.0 * 1024.0)
-        );
-    }
-
-    let optimal_concurrency = calculate_download_concurrency(&new_files, max_concurrency);
-    println!("Using {} concurrent downloads", optimal_concurrency);
+/// Streams the listings of `root_id` and all folders below it, listing at
+/// most `concurrency` folders at a time. Subfolders deeper than `max_depth`
+/// (children of the root are at depth 1) are not entered. A listing is
+/// yielded as soon as its folder is read, so parents always come before their
+/// children; folders reachable through several parents are listed once.
+/// `filter_query` narrows the files returned but never drops subfolders, and
+/// `order_by` sorts each folder's children. Dropping the stream stops the walk.
+pub fn walk_tree(
+    hub: &TDriveHub,
+    root_id: &str,
+    fields: &str,
+    filter_query: Option<&str>,
+    order_by: Option<&str>,
+    concurrency: usize,
+    max_depth: Option<usize>,
+) -> BoxStream<'static, Result<FolderListing>> {
+    let hub = Arc::clone(hub);
+    let fields = fields.to_string();
+    // subfolders are always needed to keep walking
+    let filter = filter_query
+        .map(|q| format!(" and (mimeType = '{}' or ({}))", FOLDER_MIME_TYPE, q))
+        .unwrap_or_default();
+    let order_by = order_by.map(str::to_string);
+    let list_folder = move |folder_id: String, depth: usize| {
+        let query = format!("'{}' in parents and trashed=false{}", folder_id, filter);
+        let children = query_files(
+            Arc::clone(&hub),
+            &query,
+            &fields,
+            order_by.as_deref(),
+            false,
+        )
+        .try_collect::<Vec<_>>();
+        async move { (folder_id, depth, children.await) }
+    };
 
-    let multi_progress = Arc::new(MultiProgress::new());
-    let overall_progress = multi_progress.add(ProgressBar::new(new_files.len() as u64));
-    overall_progress.set_style(
-        ProgressStyle::default_bar()
-            .template("Downloading [{bar:40.cyan/blue}] {pos}/{len} files ({percent}%) | {elapsed_precise} | ETA: {eta_precise}")
-            .unwrap()
-            .progress_chars("█▓▒░ ")
+    let state = (
+        list_folder,
+        vec![(root_id.to_string(), 0)],
+        FuturesUnordered::new(),
+        HashSet::from([root_id.to_string()]),
     );
-
-    let success_count = Arc::new(AtomicUsize::new(0));
-    let failure_count = Arc::new(AtomicUsize::new(0));
-    let retry_count = Arc::new(AtomicUsize::new(0));
-    let bytes_downloaded = Arc::new(AtomicUsize::new(0));
-
-    futures::stream::iter(new_files)
-        .map(|file| {
-            let hub = Arc::clone(&hub);
-            let target_dir = path.to_string();
-            let progress = Arc::clone(&multi_progress);
-            let overall_prog = overall_progress.clone();
-            let success = Arc::clone(&success_count);
-            let failure = Arc::clone(&failure_count);
-            let retries = Arc::clone(&retry_count);
-            let bytes = Arc::clone(&bytes_downloaded);
-
-            async move {
-                let file_id = file.id.as_ref().unwrap_or(&String::new()).to_string();
-                let file_name = file
-                    .name
-                    .as_ref()
-                    .unwrap_or(&String::from("unknown"))
-                    .to_string();
-                let file_size: u64 = file.size.unwrap_or(0).max(0) as u64;
-
-                let file_progress = progress.add(ProgressBar::new(if file_size > 0 {
-                    file_size
-                } else {
-                    100
-                }));
-                file_progress.set_style(
-                    ProgressStyle::default_bar()
-                        .template(&format!(
-                            "  {{bar:20.green/white}} {{bytes}}/{{total_bytes}} {}",
-                            file_name
-                        ))
-                        .unwrap(),
-                );
-
-                let result = download_file_with_retry(
-                    &hub,
-                    &file_id,
-                    &file_name,
-                    &target_dir,
-                    &file_progress,
-                    3, // max retries
-                    &retries,
-                    &bytes,
-                )
-                .await;
-
-                file_progress.finish_and_clear();
-                overall_prog.inc(1);
-
-                match result {
-                    Ok(_) => {
-                        success.fetch_add(1, Ordering::Relaxed);
-                        overall_prog.set_message(file_name.clone());
-                        (file_name, Ok(()))
-                    }
-                    Err(e) => {
-                        failure.fetch_add(1, Ordering::Relaxed);
-                        overall_prog.set_message(file_name.clone());
-                        (file_name, Err(e))
-                    }
-                }
+    futures::stream::try_unfold(
+        state,
+        move |(list_folder, mut pending, mut in_flight, mut seen)| async move {
+            while in_flight.len() < concurrency.max(1)
+                && let Some((folder_id, depth)) = pending.pop()
+            {
+                in_flight.push(list_folder(folder_id, depth));
             }
-        })
-        .buffer_unordered(optimal_concurrency)
-        .for_each(|(fname, result)| async move {
-            match result {
-                Ok(_) => {
-                    println!("✓ Downloaded '{}'", fname);
-                }
-                Err(e) => {
-                    eprintln!("✗ Failed '{}': {}", fname, e);
+            let Some((folder_id, depth, children)) = in_flight.next().await else {
+                return Ok(None);
+            };
+            let children = children?;
+            if max_depth.is_none_or(|max| depth + 1 < max) {
+                for child in &children {
+                    if child.mime_type.as_deref() == Some(FOLDER_MIME_TYPE)
+                        && let Some(id) = &child.id
+                        && seen.insert(id.clone())
+                    {
+                        pending.push((id.clone(), depth + 1));
+                    }
                 }
             }
+            let listing = FolderListing {
+                folder_id,
+                children,
+            };
+            Ok(Some((listing, (list_folder, pending, in_flight, seen))))
+        },
+    )
+    .boxed()
+}
+
+/// Turns the listings of a walk from `root_id` into items with their paths
+/// relative to that folder. `name` gives the path component of each child of
+/// one folder; they share one namespace, so they are named together. Folders
+/// reachable through several parents keep the first path found; other items
+/// come once per folder they are listed in.
+pub fn paths_from_listings<'a>(
+    listings: BoxStream<'a, Result<FolderListing>>,
+    root_id: &str,
+    mut name: impl FnMut(Vec<File>) -> Vec<(File, String)> + Send + 'a,
+) -> BoxStream<'a, Result<TreeItem>> {
+    let mut prefixes = HashMap::from([(root_id.to_string(), String::new())]);
+    listings
+        .map_ok(move |listing| {
+            // listings come parents first, so every prefix is known when needed
+            let prefix = prefixes[&listing.folder_id].clone();
+            let items: Vec<_> = name(listing.children)
+                .into_iter()
+                .map(|(file, name)| {
+                    let path = format!("{}{}", prefix, name);
+                    if file.mime_type.as_deref() == Some(FOLDER_MIME_TYPE) {
+                        prefixes
+                            .entry(file.id.clone().unwrap_or_default())
+                            .or_insert_with(|| format!("{}/", path));
+                    }
+                    Ok(TreeItem {
+                        file,
+                        path,
+                        parent_id: listing.folder_id.clone(),
+                    })
+                })
+                .collect();
+            futures::stream::iter(items)
         })
-        .await;
+        .try_flatten()
+        .boxed()
+}
 
-    overall_progress.finish_with_message("Download complete!");
+/// Names files by their Drive names, for [`paths_from_listings`].
+pub fn drive_names(files: Vec<File>) -> Vec<(File, String)> {
+    files
+        .into_iter()
+        .map(|file| {
+            let name = file.name.clone().unwrap_or_default();
+            (file, name)
+        })
+        .collect()
+}
 
-    let elapsed = start_time.elapsed();
-    let success = success_count.load(Ordering::Relaxed);
-    let failed = failure_count.load(Ordering::Relaxed);
-    let retries = retry_count.load(Ordering::Relaxed);
-    let total_bytes = bytes_downloaded.load(Ordering::Relaxed);
+/// Prints a `du`-style tree of the folders below `folder_id`, each with the
+/// number of files and bytes it contains including all subfolders. Totals
+/// always cover the whole tree; `depth` only limits how deep it is printed.
+pub async fn list_tree(
+    hub: TDriveHub,
+    folder_id: &str,
+    depth: Option<usize>,
+    concurrency: usize,
+) -> Result<()> {
+    let (_, root) = hub
+        .files()
+        .get(folder_id)
+        .supports_all_drives(true)
+        .param("fields", "name")
+        .add_scope(google_drive3::api::Scope::Full)
+        .doit()
+        .await?;
+    let mut listings = walk_tree(&hub, folder_id, FILE_FIELDS, None, None, concurrency, None);
+
+    let mut subfolders: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    let mut own_totals: HashMap<String, (u64, u64)> = HashMap::new();
+    while let Some(listing) = listings.try_next().await? {
+        let mut own = (0, 0);
+        let mut folders = Vec::new();
+        for child in &listing.children {
+            if child.mime_type.as_deref() == Some(FOLDER_MIME_TYPE) {
+                folders.push((
+                    child.name.clone().unwrap_or_default(),
+                    child.id.clone().unwrap_or_default(),
+                ));
+            } else {
+                own.0 += 1;
+                own.1 += child.size.unwrap_or(0).max(0) as u64;
+            }
+        }
+        folders.sort();
+        subfolders.insert(listing.folder_id.clone(), folders);
+        own_totals.insert(listing.folder_id.clone(), own);
+    }
+    let totals = roll_up_totals(&subfolders, &own_totals);
 
-    println!("\nDownload Summary:");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("Successful downloads: {}", success);
-    println!("Failed downloads: {}", failed);
-    println!("Total retries: {}", retries);
-    println!("Total time: {:?}", elapsed);
+    let (files, bytes) = totals[folder_id];
     println!(
-        "Average speed: {:.2} files/sec",
-        success as f64 / elapsed.as_secs_f64()
+        "{}/  ({} files, {})",
+        root.name.unwrap_or_else(|| folder_id.to_string()),
+        files,
+        format_size(bytes)
     );
-
-    if total_bytes > 0 {
-        let mb_per_sec = (total_bytes as f64 / (1024.0 * 1024.0)) / elapsed.as_secs_f64();
-        println!("Download speed: {:.2} MB/s", mb_per_sec);
-    }
-
+    print_subtree(&subfolders, &totals, folder_id, "", 1, depth);
     Ok(())
 }
 
-fn get_existing_local_files(path: &str) -> Result<HashSet<String>> {
-    let mut existing = HashSet::new();
+/// Adds up the `(files, bytes)` below every folder from the counts of its own
+/// files. A folder with several parents counts toward each of them.
+fn roll_up_totals(
+    subfolders: &HashMap<String, Vec<(String, String)>>,
+    own_totals: &HashMap<String, (u64, u64)>,
+) -> HashMap<String, (u64, u64)> {
+    let mut totals = HashMap::new();
+    let mut visiting = HashSet::new();
+    for id in own_totals.keys() {
+        folder_total(id, subfolders, own_totals, &mut totals, &mut visiting);
+    }
+    totals
+}
 
-    if let Ok(entries) = std::fs::read_dir(path) {
-        for entry in entries.filter_map(Result::ok) {
-            if let Ok(metadata) = entry.metadata() {
-                if metadata.is_file() {
-                    let file_name = entry.file_name().to_string_lossy().to_string();
-                    existing.insert(file_name);
-                }
-            }
-        }
+fn folder_total(
+    id: &str,
+    subfolders: &HashMap<String, Vec<(String, String)>>,
+    own_totals: &HashMap<String, (u64, u64)>,
+    totals: &mut HashMap<String, (u64, u64)>,
+    visiting: &mut HashSet<String>,
+) -> (u64, u64) {
+    if let Some(total) = totals.get(id) {
+        return *total;
     }
+    // guards against a folder that ends up inside itself
+    if !visiting.insert(id.to_string()) {
+        return (0, 0);
+    }
+    let mut total = own_totals.get(id).copied().unwrap_or_default();
+    for (_, child) in subfolders.get(id).into_iter().flatten() {
+        let child_total = folder_total(child, subfolders, own_totals, totals, visiting);
+        total.0 += child_total.0;
+        total.1 += child_total.1;
+    }
+    totals.insert(id.to_string(), total);
+    total
+}
 
-    if !existing.is_empty() {
+fn print_subtree(
+    subfolders: &HashMap<String, Vec<(String, String)>>,
+    totals: &HashMap<String, (u64, u64)>,
+    folder_id: &str,
+    indent: &str,
+    depth: usize,
+    max_depth: Option<usize>,
+) {
+    if max_depth.is_some_and(|max| depth > max) {
+        return;
+    }
+    let Some(children) = subfolders.get(folder_id) else {
+        return;
+    };
+    for (i, (name, id)) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (files, bytes) = totals.get(id).copied().unwrap_or_default();
         println!(
-            "Found {} existing files locally, will skip them",
-            existing.len()
+            "{}{}{}/  ({} files, {})",
+            indent,
+            if last { "└── " } else { "├── " },
+            name,
+            files,
+            format_size(bytes)
         );
+        let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+        print_subtree(subfolders, totals, id, &indent, depth + 1, max_depth);
     }
+}
 
-    Ok(existing)
+/// Asks `question` on stderr and reads a yes/no answer from the terminal.
+/// Fails instead of asking when stdin is not a terminal or is already used
+/// for input.
+pub fn confirm(question: &str, stdin_in_use: bool) -> Result<bool> {
+    use std::io::IsTerminal;
+    if stdin_in_use || !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "Cannot ask for confirmation without a terminal, pass --yes to go ahead"
+        ));
+    }
+    eprint!("{}? [y/N] ", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
 }
 
-fn calculate_download_concurrency(files: &[File], max_concurrency: usize) -> usize {
-    let avg_size: u64 = files
-        .iter()
-        .filter_map(|f| f.size.map(|s| s.max(0) as u64))
-        .sum::<u64>()
-        .checked_div(files.len() as u64)
-        .unwrap_or(0);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let optimal = if avg_size < 1_000_000 {
-        max_concurrency.min(50)
-    } else if avg_size < 10_000_000 {
-        max_concurrency.min(30)
-    } else if avg_size < 100_000_000 {
-        max_concurrency.min(10)
-    } else {
-        max_concurrency.min(5)
-    };
+    fn source(folder_id: Option<&str>, query: Option<&str>) -> SourceArgs {
+        SourceArgs {
+            folder_id: folder_id.map(str::to_string),
+            query: query.map(str::to_string),
+            ids_from: None,
+        }
+    }
 
-    optimal.max(1)
-}
+    #[test]
+    fn source_query_leaves_out_trashed_files() {
+        assert_eq!(
+            source_query(&source(Some("F1"), None)).as_deref(),
+            Some("'F1' in parents and trashed=false")
+        );
+        assert_eq!(
+            source_query(&source(None, Some("name contains 'x' or starred"))).as_deref(),
+            Some("(name contains 'x' or starred) and trashed=false")
+        );
+        let ids = SourceArgs {
+            ids_from: Some("ids.txt".to_string()),
+            ..source(None, None)
+        };
+        assert_eq!(source_query(&ids), None);
+    }
 
-async fn download_file_with_retry(
-    hub: &TDriveHub,
-    file_id: &str,
-    file_name: &str,
-    target_dir: &str,
-    progress: &ProgressBar,
-    max_retries: u32,
-    retry_counter: &Arc<AtomicUsize>,
-    bytes_counter: &Arc<AtomicUsize>,
-) -> Result<()> {
-    let mut attempt = 0;
-    let mut backoff = Duration::from_secs(1);
+    fn listing(folder_id: &str, children: &[(&str, &str, bool)]) -> Result<FolderListing> {
+        let children = children
+            .iter()
+            .map(|(id, name, is_folder)| File {
+                id: Some(id.to_string()),
+                name: Some(name.to_string()),
+                mime_type: Some(match is_folder {
+                    true => FOLDER_MIME_TYPE.to_string(),
+                    false => "text/plain".to_string(),
+                }),
+                ..Default::default()
+            })
+            .collect();
+        Ok(FolderListing {
+            folder_id: folder_id.to_string(),
+            children,
+        })
+    }
 
-    loop {
-        match download_single_file(hub, file_id, file_name, target_dir, progress, bytes_counter)
+    #[tokio::test]
+    async fn paths_from_listings_builds_paths_below_the_root() {
+        // `shared` is below both `a` and `b`
+        let listings = futures::stream::iter(vec![
+            listing(
+                "root",
+                &[("a", "a", true), ("b", "b", true), ("f", "top.txt", false)],
+            ),
+            listing("a", &[("shared", "s", true)]),
+            listing("shared", &[("g", "deep.txt", false)]),
+            listing("b", &[("shared", "s", true), ("f", "top.txt", false)]),
+        ])
+        .boxed();
+        let items: Vec<TreeItem> = paths_from_listings(listings, "root", drive_names)
+            .try_collect()
             .await
-        {
-            Ok(_) => return Ok(()),
-            Err(e) if attempt < max_retries => {
-                attempt += 1;
-                retry_counter.fetch_add(1, Ordering::Relaxed);
+            .unwrap();
+        let paths: Vec<(&str, &str)> = items
+            .iter()
+            .map(|item| (item.path.as_str(), item.parent_id.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("a", "root"),
+                ("b", "root"),
+                ("top.txt", "root"),
+                ("a/s", "a"),
+                ("a/s/deep.txt", "shared"),
+                ("b/s", "b"),
+                ("b/top.txt", "b"),
+            ]
+        );
+    }
 
-                let is_rate_limit = e.to_string().contains("rateLimitExceeded")
-                    || e.to_string().contains("userRateLimitExceeded")
-                    || e.to_string().contains("429");
+    #[tokio::test]
+    async fn paths_from_listings_uses_the_given_names() {
+        let listings = futures::stream::iter(vec![listing("root", &[("f", "a/b", false)])]).boxed();
+        let items: Vec<TreeItem> = paths_from_listings(listings, "root", |files| {
+            files
+                .into_iter()
+                .map(|f| (f, "a%2Fb".to_string()))
+                .collect()
+        })
+        .try_collect()
+        .await
+        .unwrap();
+        assert_eq!(items[0].path, "a%2Fb");
+    }
 
-                if is_rate_limit {
-                    backoff = Duration::from_secs(10 * attempt as u64);
-                    eprintln!(
-                        "Rate limit hit for '{}', waiting {:?} before retry {}/{}",
-                        file_name, backoff, attempt, max_retries
-                    );
-                } else {
-                    eprintln!(
-                        "Download failed for '{}': {}, retrying {}/{} in {:?}",
-                        file_name, e, attempt, max_retries, backoff
-                    );
-                }
+    #[test]
+    fn is_not_found_checks_the_status_code() {
+        let error = |code: u16| {
+            google_drive3::Error::BadRequest(serde_json::json!({
+                "error": {"code": code, "message": "File not found: 404abc."}
+            }))
+        };
+        assert!(is_not_found(&error(404)));
+        // a message mentioning 404 is not enough
+        assert!(!is_not_found(&error(403)));
+        assert!(!is_not_found(&google_drive3::Error::Cancelled));
+    }
 
-                sleep(backoff).await;
-                backoff *= 2; // Exponential backoff
-                backoff = backoff.min(Duration::from_secs(60));
-            }
-            Err(e) => return Err(e),
-        }
+    #[test]
+    fn parse_ids_takes_the_first_field_of_id_lines() {
+        let text = "id,name\n\
+                    1AbCdEfGhIjKlMnOpQrStUvWxYz012345,report.pdf\n\
+                    \n\
+                    # a comment\n\
+                    1ZyXwVuTsRqPoNmLkJiHgFeDcBa_-9876 notes.txt\n";
+        assert_eq!(
+            parse_ids(text),
+            [
+                "1AbCdEfGhIjKlMnOpQrStUvWxYz012345",
+                "1ZyXwVuTsRqPoNmLkJiHgFeDcBa_-9876"
+            ]
+        );
     }
-}
 
-async fn download_single_file(
-    hub: &TDriveHub,
-    file_id: &str,
-    file_name: &str,
-    target_dir: &str,
-    progress: &ProgressBar,
-    bytes_counter: &Arc<AtomicUsize>,
-) -> Result<()> {
-    let target_path = Path::new(target_dir).join(file_name);
-    let temp_path = target_path.with_extension("tmp");
-    let mut temp_file = FsFile::create(&temp_path)?;
-    let hub_clone = Arc::clone(&hub);
-    let download_result = hub_clone
-        .files()
-        .get(file_id)
-        .param("alt", "media")
-        .supports_all_drives(true)
-        .add_scope(google_drive3::api::Scope::Full)
-        .doit()
-        .await;
+    #[test]
+    fn parse_ids_skips_text_listing_lines() {
+        let text = "Files in folder 1AbCdEfGhIjKlMnOpQrStUvWxYz012345:\n\
+                    - report.pdf (application/pdf)\n\
+                    - notes.txt (text/plain)\n";
+        assert!(parse_ids(text).is_empty());
+    }
 
-    match download_result {
-        Ok((response, _)) => {
-            let mut body = response.into_body();
-            let mut bytes_vec = Vec::new();
-            while let Some(frame_result) = body.frame().await {
-                let frame =
-                    frame_result.map_err(|e| anyhow::anyhow!("Failed to read frame: {}", e))?;
-                if let Some(chunk) = frame.data_ref() {
-                    bytes_vec.extend_from_slice(chunk);
-                    let current_size = bytes_vec.len();
-                    progress.set_position(current_size as u64);
-                }
-            }
-            let total_bytes = bytes_vec.len();
-            temp_file.write_all(&bytes_vec)?;
-            temp_file.sync_all()?;
-            bytes_counter.fetch_add(total_bytes, Ordering::Relaxed);
-            std::fs::rename(temp_path, target_path)?;
+    #[test]
+    fn is_drive_id_checks_length_and_charset() {
+        assert!(is_drive_id("0AbCdEfGhIjKlMnOpQr"));
+        assert!(!is_drive_id("-"));
+        assert!(!is_drive_id("Files"));
+        assert!(!is_drive_id("1AbCdEfGhIjKlMnOpQrStUvWxYz01234/"));
+    }
 
-            Ok(())
-        }
-        Err(e) => {
-            let _ = std::fs::remove_file(&temp_path);
-            Err(anyhow::anyhow!(
-                "Failed to download file '{}': {}",
-                file_name,
-                e
-            ))
-        }
+    fn folders(pairs: &[(&str, &[&str])]) -> HashMap<String, Vec<(String, String)>> {
+        pairs
+            .iter()
+            .map(|(id, children)| {
+                let children = children
+                    .iter()
+                    .map(|c| (c.to_string(), c.to_string()))
+                    .collect();
+                (id.to_string(), children)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn roll_up_totals_counts_shared_folders_under_every_parent() {
+        // root -> a -> shared, root -> b -> c -> shared
+        let subfolders = folders(&[
+            ("root", &["a", "b"]),
+            ("a", &["shared"]),
+            ("b", &["c"]),
+            ("c", &["shared"]),
+            ("shared", &[]),
+        ]);
+        let own: HashMap<_, _> = [
+            ("root", (1, 10)),
+            ("a", (1, 1)),
+            ("b", (0, 0)),
+            ("c", (2, 5)),
+            ("shared", (3, 100)),
+        ]
+        .into_iter()
+        .map(|(id, t)| (id.to_string(), t))
+        .collect();
+
+        let totals = roll_up_totals(&subfolders, &own);
+        assert_eq!(totals["shared"], (3, 100));
+        assert_eq!(totals["c"], (5, 105));
+        assert_eq!(totals["b"], (5, 105));
+        assert_eq!(totals["a"], (4, 101));
+        assert_eq!(totals["root"], (10, 216));
     }
 }